@@ -0,0 +1,271 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::retry::ErrorKind;
+use crate::transporter::Transporter;
+
+/// 通信地址，6 字节 12 位 BCD 码，按书写顺序（高字节在前）保存
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MeterAddress([u8; 6]);
+
+impl MeterAddress {
+    pub fn new(bytes: [u8; 6]) -> Self {
+        Self(bytes)
+    }
+    /// 广播地址 999999999999H
+    pub fn broadcast() -> Self {
+        Self([0x99; 6])
+    }
+    /// 通配地址 AAAAAAAAAAAAH
+    pub fn wildcard() -> Self {
+        Self([0xAA; 6])
+    }
+    /// 从帧中的地址域（低字节在前）构造
+    pub fn from_wire(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 6 {
+            return Err(format!("invalid address length `{}`", bytes.len()).into());
+        }
+        let mut addr = [0; 6];
+        addr.copy_from_slice(bytes);
+        addr.reverse();
+        Ok(Self(addr))
+    }
+    pub fn bytes(&self) -> [u8; 6] {
+        self.0
+    }
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+    pub fn is_broadcast(&self) -> bool {
+        self.0 == [0x99; 6]
+    }
+    /// 含 AAH 通配字节（含缩位地址）
+    pub fn is_wildcard(&self) -> bool {
+        self.0.contains(&0xAA)
+    }
+}
+
+impl fmt::Display for MeterAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl FromStr for MeterAddress {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = hex::decode(s.replace(' ', ""))?;
+        if v.len() != 6 {
+            return Err(format!("invalid address `{}`", s).into());
+        }
+        // 每字节为 BCD 码或通配符 AAH
+        if v.iter().any(|b| *b != 0xAA && (b >> 4 > 9 || b & 0x0f > 9)) {
+            return Err(format!("invalid address `{}`", s).into());
+        }
+        let mut addr = [0; 6];
+        addr.copy_from_slice(&v);
+        Ok(Self(addr))
+    }
+}
+
+/// 序列化为书写顺序的 12 位字符串
+impl Serialize for MeterAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MeterAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl TryFrom<&str> for MeterAddress {
+    type Error = Error;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// 读通信地址时多块电表同时应答，应答帧冲突
+#[derive(Debug)]
+pub struct AddressCollision(pub String);
+
+impl fmt::Display for AddressCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "more than one meter answered reading address: {}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for AddressCollision {}
+
+/// 读通信地址（控制码 13H），命令发往通配地址，总线上必须只有一块电表。
+/// 应答帧校验码错误、无法解码或帧头地址与数据域地址不一致时返回 AddressCollision，
+/// 超时、串口及网络错误原样返回。
+pub async fn read_address<T: Transporter + ?Sized>(
+    transporter: &mut T,
+) -> Result<MeterAddress, Error> {
+    let adu: Vec<u8> = ProtocolDataUnit::read_addr()?.into();
+    let reply = match transporter.send(&adu).await {
+        Ok(Some(reply)) => reply,
+        Ok(None) => return Err("no meter answered reading address".into()),
+        // 多块电表同时应答时应答帧损坏
+        Err(e) if matches!(ErrorKind::of(&e), ErrorKind::Checksum | ErrorKind::Decode) => {
+            return Err(Box::new(AddressCollision(e.to_string())))
+        }
+        Err(e) => return Err(e),
+    };
+    // 正常应答 93H，数据域为通信地址
+    reply.check_reply(0x13)?;
+    let header = MeterAddress::from_wire(&reply.address())?;
+    let addr = MeterAddress::from_wire(&reply.payload())
+        .map_err(|e| Box::new(AddressCollision(e.to_string())))?;
+    if header != addr {
+        return Err(Box::new(AddressCollision(format!(
+            "header address `{}` differs from data address `{}`",
+            header, addr
+        ))));
+    }
+    Ok(addr)
+}
+
+/// 写通信地址（控制码 15H）。命令只能发往通配地址，总线上必须只有一块电表；
+/// 写入后读回地址确认，期间独占共享总线。
+pub async fn write_address<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    new: MeterAddress,
+) -> Result<(), Error> {
+    transporter.begin_transaction().await?;
+    let r = write_and_verify(transporter, new).await;
+    transporter.end_transaction();
+    r
+}
+
+/// 写通信地址并读回确认，调用方已开始多帧交互
+async fn write_and_verify<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    new: MeterAddress,
+) -> Result<(), Error> {
+    if new.is_broadcast() || new.is_wildcard() {
+        return Err(format!("can not write address `{}` to meter", new).into());
+    }
+    let old = read_address(transporter).await?;
+    let adu: Vec<u8> = ProtocolDataUnit::set_addr(new.to_vec())?.into();
+    match transporter.send(&adu).await? {
+        // 正常应答 95H，异常应答 D5H
+        Some(reply) => reply.check_reply(0x15)?,
+        None => return Err(format!("no response writing address of `{}`", old).into()),
+    }
+    let current = read_address(transporter).await?;
+    if current != new {
+        return Err(format!(
+            "meter address is `{}` after writing `{}` to `{}`",
+            current, new, old
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::frame::DecodeError;
+    use crate::mock::MockTransporter;
+
+    #[test]
+    fn parse() {
+        let addr: MeterAddress = "202208310002".parse().unwrap();
+        assert_eq!(addr.bytes(), [0x20, 0x22, 0x08, 0x31, 0x00, 0x02]);
+        assert_eq!(addr.to_string(), "202208310002");
+        assert!("aaaaaaaaaa02"
+            .parse::<MeterAddress>()
+            .unwrap()
+            .is_wildcard());
+        assert!("2022083100".parse::<MeterAddress>().is_err());
+        assert!("2022083100a2".parse::<MeterAddress>().is_err());
+    }
+    #[test]
+    fn from_wire() {
+        let addr = MeterAddress::from_wire(&[0x02, 0x00, 0x31, 0x08, 0x22, 0x20]).unwrap();
+        assert_eq!(addr.to_string(), "202208310002");
+    }
+    #[test]
+    fn write_address() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            let old = hex::decode("202208310002").unwrap();
+            let new = hex::decode("202208310003").unwrap();
+            t.reply("202208310002", 0x93, &[old])
+                .reply("202208310002", 0x95, &[])
+                .reply("202208310003", 0x93, &[new]);
+            super::write_address(&mut t, "202208310003".parse().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(t.sent[1].address_real_str(), "aaaaaaaaaaaa");
+            assert_eq!(hex::encode(t.sent[1].payload()), "030031082220");
+            assert!(super::write_address(&mut t, MeterAddress::wildcard())
+                .await
+                .is_err());
+            // 多块电表同时应答
+            t.replies.push_back(Err(Box::new(DecodeError(
+                "invalid frame type byte `0`".into(),
+            ))));
+            assert!(
+                super::write_address(&mut t, "202208310004".parse().unwrap())
+                    .await
+                    .is_err()
+            );
+            assert_eq!(t.sent.len(), 4);
+        })
+    }
+    #[test]
+    fn read_address() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(
+                "202208310002",
+                0x93,
+                &[hex::decode("202208310002").unwrap()],
+            )
+            .reply(
+                "202208310002",
+                0x93,
+                &[hex::decode("202208310003").unwrap()],
+            );
+            t.replies.push_back(Err(Box::new(DecodeError(
+                "invalid frame type byte `0`".into(),
+            ))));
+            t.replies
+                .push_back(Err(Box::new(io::Error::from(io::ErrorKind::BrokenPipe))));
+            let addr = super::read_address(&mut t).await.unwrap();
+            assert_eq!(addr.to_string(), "202208310002");
+            let e = super::read_address(&mut t).await.unwrap_err();
+            assert!(e.downcast_ref::<AddressCollision>().is_some());
+            let e = super::read_address(&mut t).await.unwrap_err();
+            assert!(e.downcast_ref::<AddressCollision>().is_some());
+            // 串口错误及无应答不是冲突
+            let e = super::read_address(&mut t).await.unwrap_err();
+            assert_eq!(
+                e.downcast_ref::<io::Error>().unwrap().kind(),
+                io::ErrorKind::BrokenPipe
+            );
+            let e = super::read_address(&mut t).await.unwrap_err();
+            assert_eq!(ErrorKind::of(&e), ErrorKind::Timeout);
+        })
+    }
+}
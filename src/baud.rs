@@ -0,0 +1,334 @@
+use std::fmt;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::read::read;
+use crate::transporter::Transporter;
+
+/// 修改波特率后用于确认通信的数据标识（通信地址）
+pub const VERIFY_DI: DataId = DataId(0x04000401);
+/// 收到确认后等待电表切换速率的时间，之后再按新速率重新打开串口
+pub const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// 通信速率，对应通信速率特征字 Z 中的一位
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaudRate {
+    B600,
+    B1200,
+    B2400,
+    B4800,
+    B9600,
+    B19200,
+}
+
+impl BaudRate {
+    pub fn bits_per_second(&self) -> u32 {
+        match self {
+            Self::B600 => 600,
+            Self::B1200 => 1200,
+            Self::B2400 => 2400,
+            Self::B4800 => 4800,
+            Self::B9600 => 9600,
+            Self::B19200 => 19200,
+        }
+    }
+    /// 通信速率特征字：D1~D6 依次为 600~19200bps
+    pub fn feature_word(&self) -> u8 {
+        match self {
+            Self::B600 => 0x02,
+            Self::B1200 => 0x04,
+            Self::B2400 => 0x08,
+            Self::B4800 => 0x10,
+            Self::B9600 => 0x20,
+            Self::B19200 => 0x40,
+        }
+    }
+    pub fn from_feature_word(z: u8) -> Result<Self, Error> {
+        match z {
+            0x02 => Ok(Self::B600),
+            0x04 => Ok(Self::B1200),
+            0x08 => Ok(Self::B2400),
+            0x10 => Ok(Self::B4800),
+            0x20 => Ok(Self::B9600),
+            0x40 => Ok(Self::B19200),
+            _ => Err(format!("invalid baud rate feature word `{:02x}`", z).into()),
+        }
+    }
+}
+
+impl TryFrom<u32> for BaudRate {
+    type Error = Error;
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            600 => Ok(Self::B600),
+            1200 => Ok(Self::B1200),
+            2400 => Ok(Self::B2400),
+            4800 => Ok(Self::B4800),
+            9600 => Ok(Self::B9600),
+            19200 => Ok(Self::B19200),
+            _ => Err(format!("unsupported baud rate `{}`", value).into()),
+        }
+    }
+}
+
+/// 更改通信速率命令（控制码 17H）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChangeBaudRate {
+    pub rate: BaudRate,
+}
+
+impl ChangeBaudRate {
+    pub fn new(rate: BaudRate) -> Self {
+        Self { rate }
+    }
+    pub fn to_pdu(&self, addr: &MeterAddress) -> Result<ProtocolDataUnit, Error> {
+        ProtocolDataUnit::from_cmd_2(addr.to_vec(), 0x17, &vec![vec![self.rate.feature_word()]])
+    }
+    /// 正常应答 97H 并返回相同的特征字，异常应答 D7H
+    pub fn check_reply(&self, reply: &ProtocolDataUnit) -> Result<(), Error> {
+        reply.check_reply(0x17)?;
+        let rate = match reply.payload().as_slice() {
+            [z] => BaudRate::from_feature_word(*z)?,
+            p => return Err(format!("invalid baud rate reply `{}`", hex::encode(p)).into()),
+        };
+        if rate != self.rate {
+            return Err(format!("meter acknowledged {:?}, expect {:?}", rate, self.rate).into());
+        }
+        Ok(())
+    }
+}
+
+/// 可在运行中修改波特率的 Transporter
+#[async_trait]
+pub trait BaudRateControl: Transporter {
+    fn baud_rate(&self) -> Result<u32, Error>;
+    /// 修改波特率，已打开时按新波特率重新打开
+    async fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), Error>;
+}
+
+/// 电表已确认更改通信速率，但按新速率重新打开串口或确认通信失败。
+/// source 为失败原因，rollback 为按原速率重新打开串口的结果，
+/// answered 为电表实际应答的速率，新旧速率均无应答时为 None
+#[derive(Debug)]
+pub struct BaudRateChangeError {
+    pub rate: BaudRate,
+    /// 原速率 bps
+    pub old: u32,
+    pub source: Error,
+    pub rollback: Result<(), Error>,
+    pub answered: Option<u32>,
+}
+
+impl fmt::Display for BaudRateChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "meter not responding at {}bps: {}",
+            self.rate.bits_per_second(),
+            self.source
+        )?;
+        if let Err(e) = &self.rollback {
+            return write!(f, ", reopening at {}bps failed: {}", self.old, e);
+        }
+        match self.answered {
+            Some(rate) => write!(f, ", meter answers at {}bps", rate),
+            None => write!(f, ", meter answers at neither rate"),
+        }
+    }
+}
+
+impl std::error::Error for BaudRateChangeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// 更改电表通信速率：以原速率发送命令并等待确认，等待 SETTLE_DELAY 后按新速率重新打开串口，
+/// 读通信地址确认。确认失败时再以新速率确认一次，仍失败则按原速率重新打开串口并确认，
+/// 返回 BaudRateChangeError，包含失败原因、恢复结果及电表实际应答的速率。
+pub async fn change_baud_rate<T: BaudRateControl + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    rate: BaudRate,
+) -> Result<(), Error> {
+    let old = transporter.baud_rate()?;
+    let request = ChangeBaudRate::new(rate);
+    let adu: Vec<u8> = request.to_pdu(addr)?.into();
+    match transporter.send(&adu).await? {
+        Some(reply) => request.check_reply(&reply)?,
+        None => return Err(format!("no response changing baud rate of `{}`", addr).into()),
+    }
+    tokio::time::sleep(SETTLE_DELAY).await;
+    let source = match transporter.set_baud_rate(rate.bits_per_second()).await {
+        Ok(()) => match read(transporter, addr, VERIFY_DI).await {
+            Ok(_) => return Ok(()),
+            // 电表切换较慢时第一次确认可能失败
+            Err(e) => match read(transporter, addr, VERIFY_DI).await {
+                Ok(_) => return Ok(()),
+                Err(_) => e,
+            },
+        },
+        Err(e) => e,
+    };
+    let rollback = transporter.set_baud_rate(old).await;
+    let answered = match rollback {
+        Ok(()) => read(transporter, addr, VERIFY_DI).await.ok().map(|_| old),
+        Err(_) => None,
+    };
+    Err(Box::new(BaudRateChangeError {
+        rate,
+        old,
+        source,
+        rollback,
+        answered,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::{timeout, MockTransporter};
+    use crate::retry::ErrorKind;
+
+    const ADDR: &str = "202208310002";
+
+    struct Serial {
+        inner: MockTransporter,
+        rates: Vec<u32>,
+        /// 设置为该波特率时失败
+        fail: Option<u32>,
+    }
+
+    #[async_trait]
+    impl Transporter for Serial {
+        async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+            self.inner.send(adu).await
+        }
+        async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+            self.inner.write(adu).await
+        }
+        async fn open(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+        async fn close(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl BaudRateControl for Serial {
+        fn baud_rate(&self) -> Result<u32, Error> {
+            Ok(*self.rates.last().unwrap())
+        }
+        async fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), Error> {
+            if self.fail == Some(baud_rate) {
+                return Err(format!("can not open serial port at {}bps", baud_rate).into());
+            }
+            self.rates.push(baud_rate);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn change() {
+        block_on(async {
+            let mut serial = Serial {
+                inner: MockTransporter::new(),
+                rates: vec![1200],
+                fail: None,
+            };
+            serial.inner.reply(ADDR, 0x97, &[vec![0x20]]).reply(
+                ADDR,
+                0x91,
+                &[vec![0x04, 0x00, 0x04, 0x01], hex::decode(ADDR).unwrap()],
+            );
+            let addr = ADDR.parse().unwrap();
+            change_baud_rate(&mut serial, &addr, BaudRate::B9600)
+                .await
+                .unwrap();
+            assert_eq!(serial.rates, vec![1200, 9600]);
+            assert_eq!(hex::encode(serial.inner.sent[0].payload()), "20");
+        })
+    }
+    #[test]
+    fn rollback() {
+        block_on(async {
+            let addr = ADDR.parse().unwrap();
+            let verified = &[vec![0x04, 0x00, 0x04, 0x01], hex::decode(ADDR).unwrap()];
+
+            // 第二次按新速率确认成功
+            let mut serial = Serial {
+                inner: MockTransporter::new(),
+                rates: vec![1200],
+                fail: None,
+            };
+            serial.inner.reply(ADDR, 0x97, &[vec![0x20]]);
+            serial.inner.replies.push_back(Err(timeout()));
+            serial.inner.reply(ADDR, 0x91, verified);
+            change_baud_rate(&mut serial, &addr, BaudRate::B9600)
+                .await
+                .unwrap();
+            assert_eq!(serial.rates, vec![1200, 9600]);
+
+            // 电表仍按原速率应答
+            let mut serial = Serial {
+                inner: MockTransporter::new(),
+                rates: vec![1200],
+                fail: None,
+            };
+            serial.inner.reply(ADDR, 0x97, &[vec![0x20]]);
+            serial.inner.replies.push_back(Err(timeout()));
+            serial.inner.replies.push_back(Err(timeout()));
+            serial.inner.reply(ADDR, 0x91, verified);
+            let e = change_baud_rate(&mut serial, &addr, BaudRate::B9600)
+                .await
+                .unwrap_err();
+            assert_eq!(serial.rates, vec![1200, 9600, 1200]);
+            assert_eq!(serial.inner.sent.len(), 4);
+            let e = e.downcast_ref::<BaudRateChangeError>().unwrap();
+            assert!(e.rollback.is_ok());
+            assert_eq!(e.answered, Some(1200));
+            assert!(e.to_string().ends_with("meter answers at 1200bps"));
+
+            // 新旧速率均无应答
+            let mut serial = Serial {
+                inner: MockTransporter::new(),
+                rates: vec![1200],
+                fail: None,
+            };
+            serial.inner.reply(ADDR, 0x97, &[vec![0x20]]);
+            let e = change_baud_rate(&mut serial, &addr, BaudRate::B9600)
+                .await
+                .unwrap_err();
+            let e = e.downcast_ref::<BaudRateChangeError>().unwrap();
+            assert_eq!(e.answered, None);
+            assert!(e.to_string().ends_with("meter answers at neither rate"));
+
+            // 恢复原速率也失败时保留原因
+            let mut serial = Serial {
+                inner: MockTransporter::new(),
+                rates: vec![1200],
+                fail: Some(1200),
+            };
+            serial.inner.reply(ADDR, 0x97, &[vec![0x20]]);
+            let e = change_baud_rate(&mut serial, &addr, BaudRate::B9600)
+                .await
+                .unwrap_err();
+            let e = e.downcast_ref::<BaudRateChangeError>().unwrap();
+            assert_eq!(e.old, 1200);
+            assert_eq!(ErrorKind::of(&e.source), ErrorKind::Timeout);
+            assert_eq!(
+                e.rollback.as_ref().unwrap_err().to_string(),
+                "can not open serial port at 1200bps"
+            );
+            assert_eq!(e.answered, None);
+        })
+    }
+}
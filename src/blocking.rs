@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::address::MeterAddress;
+use crate::baud::{BaudRate, BaudRateControl};
+use crate::catalog::{Catalog, DataId, Value};
+use crate::clear::{Clear, ClearConfirmation};
+use crate::client::Dlt645Client;
+use crate::datetime::DateTime;
+use crate::discovery::{Discovery, DiscoveryProgress, DiscoveryReport};
+use crate::error::ClientError;
+use crate::freeze::FreezeTime;
+use crate::output::OutputMode;
+use crate::password::{ChangePassword, OperatorCode, Password};
+use crate::probe::{MeterProfile, ProbeSet};
+use crate::remote::{CommandCipher, RelayStatus, RemoteControl};
+use crate::retry::RetryPolicy;
+use crate::security::{KeyProvider, SecuritySession};
+use crate::tariff::{ScheduleSet, TariffSchedule};
+use crate::transporter::Transporter;
+use crate::write::Write;
+
+/// 同步阻塞客户端，内部使用单线程运行时执行 Dlt645Client 的各操作。
+/// 不能在异步运行时中调用。
+pub struct BlockingClient<T: Transporter + Send> {
+    runtime: Runtime,
+    client: Dlt645Client<T>,
+}
+
+impl<T: Transporter + Send> BlockingClient<T> {
+    pub fn new(transporter: T) -> Result<Self, ClientError> {
+        Self::from_client(Dlt645Client::new(transporter))
+    }
+    pub fn from_client(client: Dlt645Client<T>) -> Result<Self, ClientError> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { runtime, client })
+    }
+    /// 异步客户端，用于修改目录、重试策略等设置
+    pub fn client(&mut self) -> &mut Dlt645Client<T> {
+        &mut self.client
+    }
+    pub fn into_inner(self) -> Dlt645Client<T> {
+        self.client
+    }
+    pub fn catalog_mut(&mut self) -> &mut Catalog {
+        self.client.catalog_mut()
+    }
+    /// 返回按 policy 重试的客户端，释放后恢复原策略
+    pub fn override_policy(&mut self, policy: RetryPolicy) -> BlockingPolicyScope<'_, T> {
+        let default = Some(self.client.set_retry_policy(policy));
+        BlockingPolicyScope {
+            client: self,
+            default,
+        }
+    }
+    /// 最近一次操作的发送次数（含重试及后续帧）
+    pub fn attempts(&self) -> u32 {
+        self.client.attempts()
+    }
+    pub fn open(&mut self) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.open())
+    }
+    pub fn close(&mut self) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.close())
+    }
+    pub fn flush_input(&mut self) -> Result<usize, ClientError> {
+        self.runtime.block_on(self.client.flush_input())
+    }
+    pub fn read_raw(&mut self, addr: &MeterAddress, di: DataId) -> Result<Vec<u8>, ClientError> {
+        self.runtime.block_on(self.client.read_raw(addr, di))
+    }
+    pub fn read(&mut self, addr: &MeterAddress, di: DataId) -> Result<Value, ClientError> {
+        self.runtime.block_on(self.client.read(addr, di))
+    }
+    pub fn read_block(
+        &mut self,
+        addr: &MeterAddress,
+        di: DataId,
+    ) -> Result<BTreeMap<DataId, Value>, ClientError> {
+        self.runtime.block_on(self.client.read_block(addr, di))
+    }
+    pub fn probe(
+        &mut self,
+        addr: &MeterAddress,
+        set: &ProbeSet,
+    ) -> Result<MeterProfile, ClientError> {
+        self.runtime.block_on(self.client.probe(addr, set))
+    }
+    pub fn write(&mut self, addr: &MeterAddress, request: &Write) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.write(addr, request))
+    }
+    pub fn read_address(&mut self) -> Result<MeterAddress, ClientError> {
+        self.runtime.block_on(self.client.read_address())
+    }
+    pub fn write_address(&mut self, new: MeterAddress) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.write_address(new))
+    }
+    pub fn discover<F: FnMut(&DiscoveryProgress)>(
+        &mut self,
+        discovery: &Discovery,
+        progress: F,
+    ) -> Result<DiscoveryReport, ClientError> {
+        self.runtime
+            .block_on(self.client.discover(discovery, progress))
+    }
+    pub fn broadcast_time(
+        &mut self,
+        now: &DateTime,
+        reference: Option<&DateTime>,
+    ) -> Result<usize, ClientError> {
+        self.runtime
+            .block_on(self.client.broadcast_time(now, reference))
+    }
+    pub fn freeze(&mut self, addr: &MeterAddress, time: FreezeTime) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.freeze(addr, time))
+    }
+    pub fn change_password(
+        &mut self,
+        addr: &MeterAddress,
+        request: &ChangePassword,
+    ) -> Result<(), ClientError> {
+        self.runtime
+            .block_on(self.client.change_password(addr, request))
+    }
+    pub fn clear(
+        &mut self,
+        addr: &MeterAddress,
+        request: &Clear,
+        confirmation: ClearConfirmation,
+    ) -> Result<(), ClientError> {
+        self.runtime
+            .block_on(self.client.clear(addr, request, confirmation))
+    }
+    pub fn remote_control<C: CommandCipher + ?Sized>(
+        &mut self,
+        addr: &MeterAddress,
+        request: &RemoteControl,
+        cipher: &C,
+    ) -> Result<RelayStatus, ClientError> {
+        self.runtime
+            .block_on(self.client.remote_control(addr, request, cipher))
+    }
+    pub fn read_relay_status(&mut self, addr: &MeterAddress) -> Result<RelayStatus, ClientError> {
+        self.runtime.block_on(self.client.read_relay_status(addr))
+    }
+    pub fn set_output(&mut self, addr: &MeterAddress, mode: OutputMode) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.set_output(addr, mode))
+    }
+    pub fn authenticate<K: KeyProvider>(
+        &mut self,
+        session: &mut SecuritySession<K>,
+    ) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.authenticate(session))
+    }
+    pub fn read_schedule(
+        &mut self,
+        addr: &MeterAddress,
+        set: ScheduleSet,
+    ) -> Result<TariffSchedule, ClientError> {
+        self.runtime.block_on(self.client.read_schedule(addr, set))
+    }
+    pub fn write_schedule(
+        &mut self,
+        addr: &MeterAddress,
+        set: ScheduleSet,
+        schedule: &TariffSchedule,
+        switch_time: &DateTime,
+        password: &Password,
+        operator: &OperatorCode,
+    ) -> Result<(), ClientError> {
+        self.runtime.block_on(self.client.write_schedule(
+            addr,
+            set,
+            schedule,
+            switch_time,
+            password,
+            operator,
+        ))
+    }
+}
+
+impl<T: BaudRateControl + Send> BlockingClient<T> {
+    pub fn change_baud_rate(
+        &mut self,
+        addr: &MeterAddress,
+        rate: BaudRate,
+    ) -> Result<(), ClientError> {
+        self.runtime
+            .block_on(self.client.change_baud_rate(addr, rate))
+    }
+}
+
+/// 临时使用其他重试策略的阻塞客户端，释放时恢复原策略
+pub struct BlockingPolicyScope<'a, T: Transporter + Send> {
+    client: &'a mut BlockingClient<T>,
+    default: Option<RetryPolicy>,
+}
+
+impl<T: Transporter + Send> Deref for BlockingPolicyScope<'_, T> {
+    type Target = BlockingClient<T>;
+    fn deref(&self) -> &Self::Target {
+        self.client
+    }
+}
+
+impl<T: Transporter + Send> DerefMut for BlockingPolicyScope<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client
+    }
+}
+
+impl<T: Transporter + Send> Drop for BlockingPolicyScope<'_, T> {
+    fn drop(&mut self) {
+        if let Some(default) = self.default.take() {
+            self.client.client.set_retry_policy(default);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    #[test]
+    fn blocking() {
+        let mut t = MockTransporter::new();
+        t.reply(ADDR, 0x93, &[hex::decode(ADDR).unwrap()]).reply(
+            ADDR,
+            0x91,
+            &[vec![0x00, 0x01, 0x00, 0x00], vec![0x00, 0x00, 0x12, 0x34]],
+        );
+        let mut client = BlockingClient::new(t).unwrap();
+        client.open().unwrap();
+        let addr = client.read_address().unwrap();
+        let value = client.read(&addr, DataId(0x00010000)).unwrap();
+        assert_eq!(value.to_string(), "12.34");
+        assert!(client.read(&addr, DataId(0x00010000)).is_err());
+        let mut scope = client.override_policy(RetryPolicy::new(2));
+        assert!(scope.read(&addr, DataId(0x00010000)).is_err());
+        assert_eq!(scope.attempts(), 2);
+        drop(scope);
+        assert_eq!(client.client().retry_policy(), &RetryPolicy::default());
+    }
+}
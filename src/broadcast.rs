@@ -0,0 +1,101 @@
+use crate::address::MeterAddress;
+use crate::datetime::{to_bcd, DateTime};
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::transporter::Transporter;
+
+/// 广播校时允许的最大偏差（秒）
+pub const MAX_OFFSET_SECS: i64 = 5 * 60;
+
+/// 广播校时命令（控制码 08H），电表不应答
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BroadcastTime {
+    now: DateTime,
+}
+
+impl BroadcastTime {
+    /// 标准规定不得在每日零点前后 5 分钟内广播校时
+    pub fn new(now: DateTime) -> Result<Self, Error> {
+        let secs = now.timestamp().rem_euclid(86400);
+        if !(MAX_OFFSET_SECS..86400 - MAX_OFFSET_SECS).contains(&secs) {
+            return Err(format!("broadcast time `{}` is within 5 minutes of midnight", now).into());
+        }
+        Ok(Self { now })
+    }
+    pub fn now(&self) -> DateTime {
+        self.now
+    }
+    /// 电表只接受不超过 ±5 分钟的校时，reference 为从总线上电表读到的时钟
+    pub fn check_offset(&self, reference: &DateTime) -> Result<(), Error> {
+        let offset = self.now.timestamp() - reference.timestamp();
+        if offset.abs() > MAX_OFFSET_SECS {
+            return Err(format!(
+                "meter clock `{}` is {}s away from `{}`, exceeds ±{}s",
+                reference, offset, self.now, MAX_OFFSET_SECS
+            )
+            .into());
+        }
+        Ok(())
+    }
+    /// 数据域 ssmmhhDDMMYY
+    pub fn to_pdu(&self) -> Result<ProtocolDataUnit, Error> {
+        let t = &self.now;
+        let data = vec![
+            to_bcd((t.year % 100) as u8),
+            to_bcd(t.month),
+            to_bcd(t.day),
+            to_bcd(t.hour),
+            to_bcd(t.minute),
+            to_bcd(t.second),
+        ];
+        ProtocolDataUnit::from_cmd_2(MeterAddress::broadcast().to_vec(), 0x08, &vec![data])
+    }
+}
+
+/// 向总线上所有电表广播校时，不等待应答，返回写入的字节数。
+/// reference 为总线上某块电表的时钟，给出时检查偏差不超过 ±5 分钟。
+pub async fn broadcast_time<T: Transporter + Send + ?Sized>(
+    transporter: &mut T,
+    now: &DateTime,
+    reference: Option<&DateTime>,
+) -> Result<usize, Error> {
+    let request = BroadcastTime::new(*now)?;
+    if let Some(reference) = reference {
+        request.check_offset(reference)?;
+    }
+    let adu: Vec<u8> = request.to_pdu()?.into();
+    transporter.write(&adu).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    #[test]
+    fn restrictions() {
+        assert!(BroadcastTime::new(DateTime::new(2023, 2, 15, 23, 56, 0).unwrap()).is_err());
+        assert!(BroadcastTime::new(DateTime::new(2023, 2, 15, 0, 4, 59).unwrap()).is_err());
+        let request = BroadcastTime::new(DateTime::new(2023, 2, 15, 0, 5, 0).unwrap()).unwrap();
+        assert!(request
+            .check_offset(&DateTime::new(2023, 2, 15, 0, 0, 0).unwrap())
+            .is_ok());
+        assert!(request
+            .check_offset(&DateTime::new(2023, 2, 15, 0, 10, 1).unwrap())
+            .is_err());
+    }
+    #[test]
+    fn broadcast() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            let now = DateTime::new(2023, 2, 15, 10, 20, 30).unwrap();
+            let n = broadcast_time(&mut t, &now, None).await.unwrap();
+            assert_eq!(n, 22);
+            assert_eq!(t.sent[0].address_real_str(), "999999999999");
+            assert_eq!(t.sent[0].c(), 0x08);
+            assert_eq!(hex::encode(t.sent[0].payload()), "302010150223");
+        })
+    }
+}
@@ -0,0 +1,762 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout_at, Instant};
+
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::transporter::Transporter;
+
+/// 库中多帧交互独占总线的最长时间
+pub const DEFAULT_HOLD: Duration = Duration::from_secs(30);
+
+/// 请求状态：排队中、已开始执行、调用方已超过截止时间放弃等待
+const QUEUED: u8 = 0;
+const DISPATCHED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// 请求优先级，队列中优先级高的请求先执行，同优先级按提交顺序
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// 例行轮询
+    Low,
+    #[default]
+    Normal,
+    /// 控制命令
+    High,
+}
+
+/// 单次请求的选项
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    pub priority: Priority,
+    /// 超过截止时间仍未执行的请求不再发送，已开始执行的请求不受影响
+    pub deadline: Option<Instant>,
+}
+
+/// 总线队列统计
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BusMetrics {
+    /// 当前排队的请求数
+    pub queued: usize,
+    pub completed: u64,
+    /// 超过截止时间未执行
+    pub expired: u64,
+    /// 执行前被调用方取消
+    pub cancelled: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    queued: AtomicUsize,
+    completed: AtomicU64,
+    expired: AtomicU64,
+    cancelled: AtomicU64,
+}
+
+enum JobKind {
+    Send(Vec<u8>),
+    Write(Vec<u8>),
+    Open,
+    Close,
+    FlushInput,
+    /// 独占总线，最长 hold
+    Lock(Duration),
+}
+
+enum Outcome {
+    Frame(Option<ProtocolDataUnit>),
+    Count(usize),
+    Done,
+    Locked(mpsc::UnboundedSender<Job>),
+}
+
+impl Outcome {
+    fn frame(self) -> Result<Option<ProtocolDataUnit>, Error> {
+        match self {
+            Self::Frame(r) => Ok(r),
+            _ => Err("unexpected bus outcome".into()),
+        }
+    }
+    fn count(self) -> Result<usize, Error> {
+        match self {
+            Self::Count(n) => Ok(n),
+            _ => Err("unexpected bus outcome".into()),
+        }
+    }
+}
+
+struct Job {
+    priority: Priority,
+    seq: u64,
+    deadline: Option<Instant>,
+    kind: JobKind,
+    state: Arc<AtomicU8>,
+    reply: oneshot::Sender<Result<Outcome, Error>>,
+}
+
+impl Job {
+    fn key(&self) -> (Priority, Reverse<u64>) {
+        (self.priority, Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// 独占 Transporter 的总线任务，按优先级逐个执行请求
+pub struct Bus<T: Transporter> {
+    transporter: T,
+    rx: mpsc::UnboundedReceiver<Job>,
+    counters: Arc<Counters>,
+}
+
+impl<T: Transporter + Send> Bus<T> {
+    /// 执行请求直到所有 BusHandle 都被释放，返回 Transporter
+    pub async fn run(mut self) -> T {
+        let mut queue = BinaryHeap::new();
+        loop {
+            while let Ok(job) = self.rx.try_recv() {
+                queue.push(job);
+            }
+            let job = match queue.pop() {
+                Some(job) => job,
+                None => match self.rx.recv().await {
+                    Some(job) => job,
+                    None => return self.transporter,
+                },
+            };
+            // 加锁后只执行 BusGuard 的请求，直到 BusGuard 被释放或超过 hold
+            if let Some((mut exclusive, until)) = self.execute(job).await {
+                while let Ok(Some(job)) = timeout_at(until, exclusive.recv()).await {
+                    self.execute(job).await;
+                }
+                exclusive.close();
+                while let Ok(job) = exclusive.try_recv() {
+                    self.counters.queued.fetch_sub(1, AtomicOrdering::Relaxed);
+                    self.counters.expired.fetch_add(1, AtomicOrdering::Relaxed);
+                    let _ = job.reply.send(Err("bus lock expired".into()));
+                }
+            }
+        }
+    }
+    /// 执行一个请求，加锁请求成功时返回 BusGuard 的请求队列及锁的截止时间
+    async fn execute(&mut self, job: Job) -> Option<(mpsc::UnboundedReceiver<Job>, Instant)> {
+        self.counters.queued.fetch_sub(1, AtomicOrdering::Relaxed);
+        // 调用方超过截止时间放弃等待的请求不再发送
+        let abandoned = job
+            .state
+            .compare_exchange(
+                QUEUED,
+                DISPATCHED,
+                AtomicOrdering::AcqRel,
+                AtomicOrdering::Acquire,
+            )
+            .is_err();
+        if abandoned || job.deadline.is_some_and(|d| d <= Instant::now()) {
+            self.counters.expired.fetch_add(1, AtomicOrdering::Relaxed);
+            let _ = job
+                .reply
+                .send(Err("deadline exceeded before sending".into()));
+            return None;
+        }
+        if job.reply.is_closed() {
+            self.counters
+                .cancelled
+                .fetch_add(1, AtomicOrdering::Relaxed);
+            return None;
+        }
+        let outcome = match job.kind {
+            JobKind::Send(adu) => self.transporter.send(&adu).await.map(Outcome::Frame),
+            JobKind::Write(adu) => self.transporter.write(&adu).await.map(Outcome::Count),
+            JobKind::Open => self.transporter.open().await.map(|_| Outcome::Done),
+            JobKind::Close => self.transporter.close().await.map(|_| Outcome::Done),
+            JobKind::FlushInput => self.transporter.flush_input().await.map(Outcome::Count),
+            JobKind::Lock(hold) => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                return match job.reply.send(Ok(Outcome::Locked(tx))) {
+                    Ok(()) => {
+                        self.counters
+                            .completed
+                            .fetch_add(1, AtomicOrdering::Relaxed);
+                        Some((rx, Instant::now() + hold))
+                    }
+                    Err(_) => {
+                        self.counters
+                            .cancelled
+                            .fetch_add(1, AtomicOrdering::Relaxed);
+                        None
+                    }
+                };
+            }
+        };
+        self.counters
+            .completed
+            .fetch_add(1, AtomicOrdering::Relaxed);
+        let _ = job.reply.send(outcome);
+        None
+    }
+}
+
+type Reply = oneshot::Receiver<Result<Outcome, Error>>;
+
+/// 向总线任务提交请求
+#[derive(Clone)]
+struct Submitter {
+    tx: mpsc::UnboundedSender<Job>,
+    seq: Arc<AtomicU64>,
+    counters: Arc<Counters>,
+    /// 请求队列已关闭时的错误
+    closed: &'static str,
+}
+
+impl Submitter {
+    fn submit(
+        &self,
+        kind: JobKind,
+        options: RequestOptions,
+    ) -> Result<(Reply, Arc<AtomicU8>), Error> {
+        let (reply, rx) = oneshot::channel();
+        let state = Arc::new(AtomicU8::new(QUEUED));
+        let job = Job {
+            priority: options.priority,
+            seq: self.seq.fetch_add(1, AtomicOrdering::Relaxed),
+            deadline: options.deadline,
+            kind,
+            state: state.clone(),
+            reply,
+        };
+        self.counters.queued.fetch_add(1, AtomicOrdering::Relaxed);
+        if self.tx.send(job).is_err() {
+            self.counters.queued.fetch_sub(1, AtomicOrdering::Relaxed);
+            return Err(self.closed.into());
+        }
+        Ok((rx, state))
+    }
+    /// 截止时间只限制排队：超过截止时间仍未执行的请求不再发送，已开始执行的请求等待其结果
+    async fn request(&self, kind: JobKind, options: RequestOptions) -> Result<Outcome, Error> {
+        let (mut rx, state) = self.submit(kind, options)?;
+        let r = match options.deadline {
+            Some(deadline) => match timeout_at(deadline, &mut rx).await {
+                Ok(r) => r,
+                Err(_) => match state.compare_exchange(
+                    QUEUED,
+                    ABANDONED,
+                    AtomicOrdering::AcqRel,
+                    AtomicOrdering::Acquire,
+                ) {
+                    Ok(_) => return Err("deadline exceeded waiting for bus".into()),
+                    Err(_) => rx.await,
+                },
+            },
+            None => rx.await,
+        };
+        r.map_err(|_| Error::from(self.closed))?
+    }
+    async fn send(
+        &self,
+        adu: &[u8],
+        options: RequestOptions,
+    ) -> Result<Option<ProtocolDataUnit>, Error> {
+        self.request(JobKind::Send(adu.to_vec()), options)
+            .await?
+            .frame()
+    }
+}
+
+/// 共享总线的句柄，可克隆后在多个任务中使用，请求经 Bus 任务串行执行。
+/// 作为 Transporter 使用时按 options 中的优先级和截止时间提交；
+/// 丢弃尚未完成的请求即取消，未开始执行的请求不会发送。
+/// 队列按帧调度，库中的多帧交互（后续帧、写费率时段、写地址后读回）经 begin_transaction
+/// 自动独占总线，最长 hold；其他多帧交互需先用 lock 独占总线。
+pub struct BusHandle {
+    inner: Submitter,
+    options: RequestOptions,
+    hold: Duration,
+    /// 多帧交互期间持有的锁及嵌套层数
+    transaction: Option<(BusGuard, usize)>,
+}
+
+/// 克隆的句柄不共享多帧交互持有的锁
+impl Clone for BusHandle {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            options: self.options,
+            hold: self.hold,
+            transaction: None,
+        }
+    }
+}
+
+impl BusHandle {
+    /// 创建句柄及总线任务，总线任务需由调用方运行
+    pub fn new<T: Transporter + Send>(transporter: T) -> (Self, Bus<T>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let counters = Arc::new(Counters::default());
+        let handle = Self {
+            inner: Submitter {
+                tx,
+                seq: Arc::new(AtomicU64::new(0)),
+                counters: counters.clone(),
+                closed: "bus task stopped",
+            },
+            options: RequestOptions::default(),
+            hold: DEFAULT_HOLD,
+            transaction: None,
+        };
+        let bus = Bus {
+            transporter,
+            rx,
+            counters,
+        };
+        (handle, bus)
+    }
+    /// 创建句柄并在当前运行时中启动总线任务
+    pub fn spawn<T: Transporter + Send + 'static>(transporter: T) -> Self {
+        let (handle, bus) = Self::new(transporter);
+        tokio::spawn(bus.run());
+        handle
+    }
+    /// 使用指定优先级的句柄
+    pub fn with_priority(&self, priority: Priority) -> Self {
+        let mut handle = self.clone();
+        handle.options.priority = priority;
+        handle
+    }
+    /// 使用指定截止时间的句柄
+    pub fn with_deadline(&self, deadline: Option<Instant>) -> Self {
+        let mut handle = self.clone();
+        handle.options.deadline = deadline;
+        handle
+    }
+    /// 多帧交互独占总线最长 hold 的句柄
+    pub fn with_hold(&self, hold: Duration) -> Self {
+        let mut handle = self.clone();
+        handle.hold = hold;
+        handle
+    }
+    pub fn metrics(&self) -> BusMetrics {
+        let counters = &self.inner.counters;
+        BusMetrics {
+            queued: counters.queued.load(AtomicOrdering::Relaxed),
+            completed: counters.completed.load(AtomicOrdering::Relaxed),
+            expired: counters.expired.load(AtomicOrdering::Relaxed),
+            cancelled: counters.cancelled.load(AtomicOrdering::Relaxed),
+        }
+    }
+    /// 按 options 发送并等待应答
+    pub async fn send_with(
+        &self,
+        adu: &[u8],
+        options: RequestOptions,
+    ) -> Result<Option<ProtocolDataUnit>, Error> {
+        self.inner.send(adu, options).await
+    }
+    /// 按句柄的优先级和截止时间排队，轮到时独占总线直到返回的 BusGuard 被释放或超过 hold，
+    /// 期间其他请求（含高优先级请求）都等待。超过 hold 后 BusGuard 的请求返回错误。
+    pub async fn lock(&self, hold: Duration) -> Result<BusGuard, Error> {
+        match self
+            .inner
+            .request(JobKind::Lock(hold), self.options)
+            .await?
+        {
+            Outcome::Locked(tx) => Ok(BusGuard {
+                inner: Submitter {
+                    tx,
+                    seq: self.inner.seq.clone(),
+                    counters: self.inner.counters.clone(),
+                    closed: "bus lock expired",
+                },
+            }),
+            _ => Err("unexpected bus outcome".into()),
+        }
+    }
+    /// 多帧交互期间经持有的锁执行，否则按句柄的选项排队
+    async fn route(&mut self, kind: JobKind) -> Result<Outcome, Error> {
+        if let Some((guard, _)) = &self.transaction {
+            let r = guard.inner.request(kind, RequestOptions::default()).await;
+            // 锁已超时释放，之后的请求重新排队
+            if guard.inner.tx.is_closed() {
+                self.transaction = None;
+            }
+            return r;
+        }
+        self.inner.request(kind, self.options).await
+    }
+}
+
+#[async_trait]
+impl Transporter for BusHandle {
+    async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+        self.route(JobKind::Send(adu.to_vec())).await?.frame()
+    }
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        self.route(JobKind::Write(adu.to_vec())).await?.count()
+    }
+    async fn open(&mut self) -> Result<(), Error> {
+        self.route(JobKind::Open).await.map(|_| ())
+    }
+    async fn close(&mut self) -> Result<(), Error> {
+        self.route(JobKind::Close).await.map(|_| ())
+    }
+    async fn flush_input(&mut self) -> Result<usize, Error> {
+        self.route(JobKind::FlushInput).await?.count()
+    }
+    fn begin_transaction(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            match &mut self.transaction {
+                Some((guard, depth)) if !guard.inner.tx.is_closed() => *depth += 1,
+                _ => {
+                    let guard = self.lock(self.hold).await?;
+                    let depth = self.transaction.take().map_or(0, |(_, depth)| depth);
+                    self.transaction = Some((guard, depth + 1));
+                }
+            }
+            Ok(())
+        })
+    }
+    fn end_transaction(&mut self) {
+        if let Some((_, depth)) = &mut self.transaction {
+            *depth -= 1;
+            if *depth == 0 {
+                self.transaction = None;
+            }
+        }
+    }
+}
+
+/// 独占总线的 Transporter，由 BusHandle::lock 获得，释放后总线继续执行排队的请求
+pub struct BusGuard {
+    inner: Submitter,
+}
+
+#[async_trait]
+impl Transporter for BusGuard {
+    async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+        self.inner.send(adu, RequestOptions::default()).await
+    }
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        self.inner
+            .request(JobKind::Write(adu.to_vec()), RequestOptions::default())
+            .await?
+            .count()
+    }
+    async fn open(&mut self) -> Result<(), Error> {
+        self.inner
+            .request(JobKind::Open, RequestOptions::default())
+            .await
+            .map(|_| ())
+    }
+    async fn close(&mut self) -> Result<(), Error> {
+        self.inner
+            .request(JobKind::Close, RequestOptions::default())
+            .await
+            .map(|_| ())
+    }
+    async fn flush_input(&mut self) -> Result<usize, Error> {
+        self.inner
+            .request(JobKind::FlushInput, RequestOptions::default())
+            .await?
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future::join;
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::catalog::DataId;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    /// 每帧耗时 delay 的 Transporter
+    struct Slow {
+        inner: MockTransporter,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl Transporter for Slow {
+        async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+            tokio::time::sleep(self.delay).await;
+            self.inner.send(adu).await
+        }
+        async fn open(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+        async fn close(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn read(di0: u8) -> Vec<u8> {
+        ProtocolDataUnit::from_cmd_2(
+            hex::decode(ADDR).unwrap(),
+            0x11,
+            &vec![vec![0x00, 0x00, 0x00, di0]],
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn options(priority: Priority) -> RequestOptions {
+        RequestOptions {
+            priority,
+            deadline: None,
+        }
+    }
+
+    /// 发送的各帧 DI0
+    fn sent(t: &MockTransporter) -> Vec<u8> {
+        t.sent.iter().map(|pdu| pdu.payload()[0]).collect()
+    }
+
+    #[test]
+    fn priorities() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            for _ in 0..4 {
+                t.reply(ADDR, 0x91, &[]);
+            }
+            let (bus, actor) = BusHandle::new(t);
+            let mut pending = vec![];
+            for (di0, priority) in [
+                (1, Priority::Low),
+                (2, Priority::Normal),
+                (3, Priority::High),
+                (4, Priority::Normal),
+            ] {
+                let job = bus
+                    .inner
+                    .submit(JobKind::Send(read(di0)), options(priority));
+                pending.push(job.unwrap().0);
+            }
+            assert_eq!(bus.metrics().queued, 4);
+            let (t, _) = join(actor.run(), async move {
+                for rx in pending {
+                    assert!(rx.await.unwrap().is_ok());
+                }
+                drop(bus);
+            })
+            .await;
+            // 高优先级先发送，同优先级按提交顺序
+            assert_eq!(sent(&t), [3, 2, 4, 1]);
+        })
+    }
+    #[test]
+    fn expired() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x91, &[]);
+            let (bus, actor) = BusHandle::new(t);
+            let expired = RequestOptions {
+                priority: Priority::High,
+                deadline: Some(Instant::now() - Duration::from_millis(1)),
+            };
+            let (expired, _) = bus.inner.submit(JobKind::Send(read(1)), expired).unwrap();
+            let (normal, _) = bus
+                .inner
+                .submit(JobKind::Send(read(2)), options(Priority::Normal))
+                .unwrap();
+            let (t, metrics) = join(actor.run(), async move {
+                let e = match expired.await.unwrap() {
+                    Err(e) => e,
+                    Ok(_) => panic!("expired request was sent"),
+                };
+                assert!(e.to_string().contains("deadline"));
+                assert!(normal.await.unwrap().is_ok());
+                let metrics = bus.metrics();
+                drop(bus);
+                metrics
+            })
+            .await;
+            assert_eq!(sent(&t), [2]);
+            assert_eq!(metrics.expired, 1);
+            assert_eq!(metrics.completed, 1);
+        })
+    }
+    #[test]
+    fn cancelled() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x91, &[]);
+            let (bus, actor) = BusHandle::new(t);
+            let (cancelled, _) = bus
+                .inner
+                .submit(JobKind::Send(read(1)), options(Priority::High))
+                .unwrap();
+            drop(cancelled);
+            let (normal, _) = bus
+                .inner
+                .submit(JobKind::Send(read(2)), options(Priority::Normal))
+                .unwrap();
+            let (t, metrics) = join(actor.run(), async move {
+                assert!(normal.await.unwrap().is_ok());
+                let metrics = bus.metrics();
+                drop(bus);
+                metrics
+            })
+            .await;
+            assert_eq!(sent(&t), [2]);
+            assert_eq!(
+                metrics,
+                BusMetrics {
+                    queued: 0,
+                    completed: 1,
+                    expired: 0,
+                    cancelled: 1,
+                }
+            );
+        })
+    }
+    #[test]
+    fn transaction() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            for _ in 0..3 {
+                t.reply(ADDR, 0x91, &[]);
+            }
+            let (bus, actor) = BusHandle::new(t);
+            let (t, _) = join(actor.run(), async move {
+                let mut guard = bus
+                    .with_priority(Priority::Low)
+                    .lock(DEFAULT_HOLD)
+                    .await
+                    .unwrap();
+                // 独占期间提交的高优先级请求等待事务结束
+                let (high, _) = bus
+                    .inner
+                    .submit(JobKind::Send(read(9)), options(Priority::High))
+                    .unwrap();
+                guard.send(&read(1)).await.unwrap();
+                guard.send(&read(2)).await.unwrap();
+                drop(guard);
+                assert!(high.await.unwrap().is_ok());
+                drop(bus);
+            })
+            .await;
+            assert_eq!(sent(&t), [1, 2, 9]);
+        })
+    }
+    #[test]
+    fn deadline() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x91, &[]).reply(ADDR, 0x91, &[]);
+            let t = Slow {
+                inner: t,
+                delay: Duration::from_millis(50),
+            };
+            let (bus, actor) = BusHandle::new(t);
+            let (t, metrics) = join(actor.run(), async move {
+                // 执行期间超过截止时间仍返回应答
+                let deadline = Some(Instant::now() + Duration::from_millis(10));
+                let mut sending = bus.with_deadline(deadline);
+                assert!(sending.send(&read(1)).await.unwrap().is_some());
+                // 排队期间超过截止时间，不再发送
+                let guard = bus.lock(DEFAULT_HOLD).await.unwrap();
+                let deadline = Some(Instant::now() + Duration::from_millis(10));
+                let mut queued = bus.with_deadline(deadline);
+                let e = queued.send(&read(2)).await.unwrap_err();
+                assert_eq!(e.to_string(), "deadline exceeded waiting for bus");
+                drop(guard);
+                // 已放弃的请求在其后提交的请求之前出队
+                bus.send_with(&read(3), RequestOptions::default())
+                    .await
+                    .unwrap();
+                let metrics = bus.metrics();
+                drop(sending);
+                drop(queued);
+                drop(bus);
+                metrics
+            })
+            .await;
+            assert_eq!(sent(&t.inner), [1, 3]);
+            assert_eq!(metrics.completed, 3);
+            assert_eq!(metrics.expired, 1);
+        })
+    }
+    #[test]
+    fn lock_expired() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x91, &[]);
+            let (mut bus, actor) = BusHandle::new(t);
+            let (t, _) = join(actor.run(), async move {
+                let mut guard = bus.lock(Duration::from_millis(10)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                let e = guard.send(&read(1)).await.unwrap_err();
+                assert_eq!(e.to_string(), "bus lock expired");
+                // 锁超时后其他请求继续执行
+                assert!(bus.send(&read(2)).await.unwrap().is_some());
+                drop(bus);
+            })
+            .await;
+            assert_eq!(sent(&t), [2]);
+        })
+    }
+    #[test]
+    fn follow_up() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            let di = vec![0x00, 0x00, 0x00, 0x01];
+            t.reply(ADDR, 0xB1, &[di.clone(), vec![0x01]])
+                .reply(ADDR, 0x92, &[di.clone(), vec![0x02], vec![0x01]])
+                .reply(ADDR, 0x91, &[]);
+            let t = Slow {
+                inner: t,
+                delay: Duration::from_millis(20),
+            };
+            let (bus, actor) = BusHandle::new(t);
+            let mut polling = bus.with_priority(Priority::Low);
+            let mut control = bus.with_priority(Priority::High);
+            drop(bus);
+            let addr = ADDR.parse().unwrap();
+            let (t, _) = join(
+                actor.run(),
+                join(
+                    async move {
+                        let data = crate::read::read(&mut polling, &addr, DataId(1)).await;
+                        assert_eq!(data.unwrap(), [0x01, 0x02]);
+                    },
+                    async move {
+                        // 在第一帧执行期间提交，等待后续帧读完
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                        control.send(&read(9)).await.unwrap();
+                    },
+                ),
+            )
+            .await;
+            assert_eq!(sent(&t.inner), [1, 1, 9]);
+        })
+    }
+}
@@ -0,0 +1,908 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{Error, Exception};
+use crate::frame::ProtocolDataUnit;
+
+/// 数据标识 DI3 DI2 DI1 DI0，按书写顺序保存，如 0x0001FF00
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DataId(pub u32);
+
+impl DataId {
+    pub fn new(di3: u8, di2: u8, di1: u8, di0: u8) -> Self {
+        Self(u32::from_be_bytes([di3, di2, di1, di0]))
+    }
+    /// [DI3, DI2, DI1, DI0]
+    pub fn bytes(&self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
+    /// 从数据域（已减 33H，DI0 在前）中取出数据标识
+    pub fn from_wire(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 4 {
+            return Err(format!("data id too short `{}`", hex::encode(bytes)).into());
+        }
+        Ok(Self(u32::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3],
+        ])))
+    }
+    /// 数据块标识中 FFH 所在的字节位置（0 为 DI3），没有或有多个时为 None
+    pub fn wildcard(&self) -> Option<usize> {
+        let bytes = self.bytes();
+        let mut pos = bytes.iter().enumerate().filter(|(_, b)| **b == 0xff);
+        match (pos.next(), pos.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        }
+    }
+    pub fn is_block(&self) -> bool {
+        self.wildcard().is_some()
+    }
+    /// 替换第 index 个字节（0 为 DI3）
+    pub fn with_byte(&self, index: usize, value: u8) -> Self {
+        let mut bytes = self.bytes();
+        bytes[index] = value;
+        Self(u32::from_be_bytes(bytes))
+    }
+}
+
+impl fmt::Display for DataId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x}", self.0)
+    }
+}
+
+impl FromStr for DataId {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_start_matches("0x").replace(' ', "");
+        if s.len() != 8 {
+            return Err(format!("invalid data id `{}`", s).into());
+        }
+        Ok(Self(u32::from_str_radix(&s, 16)?))
+    }
+}
+
+/// 序列化为 8 位十六进制字符串
+impl Serialize for DataId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DataId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<u32> for DataId {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+/// 数据格式，对应标准附录中的格式串，如 XXXXXX.XX、YYMMDDhhmm
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    /// BCD 定点数，signed 时最高字节的最高位为符号位
+    Decimal {
+        len: usize,
+        decimals: u8,
+        signed: bool,
+    },
+    /// BCD 数字串，如 NNNNNNNNNNNN、YYMMDDWW、hhmmss
+    Digits {
+        len: usize,
+    },
+    Ascii {
+        len: usize,
+    },
+    Raw {
+        len: usize,
+    },
+    /// 多个字段依次排列，如 XX.XXXX YYMMDDhhmm
+    Composite(Vec<DataFormat>),
+    /// 同一格式的字段重复至多 max 次，如 MMDDNN*14
+    List {
+        item: Box<DataFormat>,
+        max: usize,
+    },
+}
+
+impl DataFormat {
+    /// 数据长度（字节），List 为最大长度
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Decimal { len, .. }
+            | Self::Digits { len }
+            | Self::Ascii { len }
+            | Self::Raw { len } => *len,
+            Self::Composite(fields) => fields.iter().map(|f| f.len()).sum(),
+            Self::List { item, max } => item.len() * max,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// 解析格式串
+    pub fn parse(notation: &str) -> Result<Self, Error> {
+        let tokens = notation.split_whitespace().collect::<Vec<_>>();
+        match tokens.len() {
+            0 => Err("empty data format".into()),
+            1 => Self::parse_token(tokens[0]),
+            _ => Ok(Self::Composite(
+                tokens
+                    .into_iter()
+                    .map(Self::parse_token)
+                    .collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+    fn parse_token(token: &str) -> Result<Self, Error> {
+        let invalid = || -> Error { format!("invalid data format `{}`", token).into() };
+        if let Some((item, max)) = token.split_once('*') {
+            let item = Self::parse_token(item)?;
+            let max = max.parse::<usize>().map_err(|_| invalid())?;
+            return Ok(Self::List {
+                item: Box::new(item),
+                max,
+            });
+        }
+        if let Some((kind, len)) = token.split_once(':') {
+            let len = len.parse::<usize>().map_err(|_| invalid())?;
+            return match kind {
+                "ascii" => Ok(Self::Ascii { len }),
+                "raw" => Ok(Self::Raw { len }),
+                _ => Err(invalid()),
+            };
+        }
+        let (signed, body) = match token.strip_prefix('±').or_else(|| token.strip_prefix('-')) {
+            Some(body) => (true, body),
+            None => (false, token),
+        };
+        if !body.is_empty() && body.chars().all(|c| c == 'X' || c == '.') {
+            let digits = body.chars().filter(|c| *c == 'X').count();
+            let decimals = match body.split_once('.') {
+                Some((_, d)) if !d.contains('.') => d.len(),
+                Some(_) => return Err(invalid()),
+                None => 0,
+            };
+            if digits % 2 != 0 {
+                return Err(invalid());
+            }
+            return Ok(Self::Decimal {
+                len: digits / 2,
+                decimals: decimals as u8,
+                signed,
+            });
+        }
+        if !signed
+            && !body.is_empty()
+            && body.len() % 2 == 0
+            && body.chars().all(|c| c.is_ascii_alphabetic())
+        {
+            return Ok(Self::Digits {
+                len: body.len() / 2,
+            });
+        }
+        Err(invalid())
+    }
+    /// 解码数据（已减 33H，低字节在前）
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value, Error> {
+        let exact = |len: usize| -> Result<(), Error> {
+            if bytes.len() != len {
+                return Err(format!("expect {} bytes, got `{}`", len, hex::encode(bytes)).into());
+            }
+            Ok(())
+        };
+        match self {
+            Self::Decimal {
+                len,
+                decimals,
+                signed,
+            } => {
+                exact(*len)?;
+                let mut be = bytes.to_vec();
+                be.reverse();
+                let negative = *signed && be.first().is_some_and(|b| b & 0x80 != 0);
+                if negative {
+                    be[0] &= 0x7f;
+                }
+                let mut raw = 0i64;
+                for b in be.iter() {
+                    let (hi, lo) = (b >> 4, b & 0x0f);
+                    if hi > 9 || lo > 9 {
+                        return Err(format!("invalid bcd `{}`", hex::encode(bytes)).into());
+                    }
+                    raw = raw * 100 + (hi * 10 + lo) as i64;
+                }
+                Ok(Value::Number {
+                    raw: if negative { -raw } else { raw },
+                    decimals: *decimals,
+                })
+            }
+            Self::Digits { len } => {
+                exact(*len)?;
+                let mut be = bytes.to_vec();
+                be.reverse();
+                Ok(Value::Text(hex::encode(be)))
+            }
+            Self::Ascii { len } => {
+                exact(*len)?;
+                let mut be = bytes.to_vec();
+                be.reverse();
+                let s = be
+                    .iter()
+                    .filter(|b| **b != 0)
+                    .map(|b| *b as char)
+                    .collect::<String>();
+                Ok(Value::Text(s.trim().to_string()))
+            }
+            Self::Raw { len } => {
+                exact(*len)?;
+                Ok(Value::Bytes(bytes.to_vec()))
+            }
+            Self::Composite(fields) => {
+                exact(self.len())?;
+                let mut values = vec![];
+                let mut offset = 0;
+                for f in fields.iter() {
+                    values.push(f.decode(&bytes[offset..offset + f.len()])?);
+                    offset += f.len();
+                }
+                Ok(Value::List(values))
+            }
+            Self::List { item, max } => {
+                let n = item.len();
+                if n == 0 || !bytes.len().is_multiple_of(n) || bytes.len() / n > *max {
+                    return Err(format!("invalid list length `{}`", hex::encode(bytes)).into());
+                }
+                // 数据块内各字段依次排列，第一项在低地址
+                let values = bytes
+                    .chunks(n)
+                    .map(|c| item.decode(c))
+                    .collect::<Result<_, _>>()?;
+                Ok(Value::List(values))
+            }
+        }
+    }
+}
+
+/// 解码后的数据
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// 定点数，实际值为 raw / 10^decimals
+    Number {
+        raw: i64,
+        decimals: u8,
+    },
+    Text(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number { raw, decimals } => Some(*raw as f64 / 10f64.powi(*decimals as i32)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number { raw, decimals } => {
+                let scale = 10i64.pow(*decimals as u32);
+                let sign = if *raw < 0 { "-" } else { "" };
+                if *decimals == 0 {
+                    write!(f, "{}{}", sign, raw.abs())
+                } else {
+                    let (int, frac) = (raw.abs() / scale, raw.abs() % scale);
+                    write!(
+                        f,
+                        "{}{}.{:0width$}",
+                        sign,
+                        int,
+                        frac,
+                        width = *decimals as usize
+                    )
+                }
+            }
+            Self::Text(s) => f.write_str(s),
+            Self::Bytes(b) => f.write_str(&hex::encode(b)),
+            Self::List(values) => {
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                write!(f, "[{}]", values.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    pub fn readable(&self) -> bool {
+        !matches!(self, Self::Write)
+    }
+    pub fn writable(&self) -> bool {
+        !matches!(self, Self::Read)
+    }
+}
+
+/// 数据项定义
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataItem {
+    pub id: DataId,
+    pub name: String,
+    pub format: DataFormat,
+    pub unit: Option<String>,
+    pub access: Access,
+}
+
+impl DataItem {
+    pub fn len(&self) -> usize {
+        self.format.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.format.is_empty()
+    }
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value, Error> {
+        self.format.decode(bytes)
+    }
+}
+
+/// 数据标识目录，用于按数据标识解码数据项和数据块
+#[derive(Clone, Debug, Default)]
+pub struct Catalog {
+    // 运行时注册的厂家自定义数据项
+    items: HashMap<DataId, DataItem>,
+}
+
+/// 数据项定义文件中的一项
+#[derive(Debug, Deserialize)]
+struct ItemDef {
+    id: String,
+    name: String,
+    format: String,
+    unit: Option<String>,
+    access: Access,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemDefs {
+    item: Vec<ItemDef>,
+}
+
+impl TryFrom<ItemDef> for DataItem {
+    type Error = Error;
+    fn try_from(def: ItemDef) -> Result<Self, Self::Error> {
+        Ok(DataItem {
+            id: def.id.parse()?,
+            name: def.name,
+            format: DataFormat::parse(&def.format)?,
+            unit: def.unit,
+            access: def.access,
+        })
+    }
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn lookup(&self, id: DataId) -> Option<DataItem> {
+        standard(id).or_else(|| self.items.get(&id).cloned())
+    }
+    /// 注册厂家自定义数据项，与标准数据标识或已注册的数据标识冲突时返回错误
+    pub fn register(&mut self, item: DataItem) -> Result<(), Error> {
+        if item.id.is_block() {
+            return Err(format!("can not register block data id `{}`", item.id).into());
+        }
+        if item.is_empty() {
+            return Err(format!("data item `{}` has no data", item.id).into());
+        }
+        if let Some(std) = standard(item.id) {
+            return Err(format!(
+                "data id `{}` conflicts with standard item `{}`",
+                item.id, std.name
+            )
+            .into());
+        }
+        if let Some(old) = self.items.get(&item.id) {
+            return Err(
+                format!("data id `{}` already registered as `{}`", item.id, old.name).into(),
+            );
+        }
+        self.items.insert(item.id, item);
+        Ok(())
+    }
+    pub fn unregister(&mut self, id: DataId) -> Option<DataItem> {
+        self.items.remove(&id)
+    }
+    /// 从 TOML 加载数据项定义，任一项出错时不注册任何数据项
+    ///
+    /// ```toml
+    /// [[item]]
+    /// id = "04a00001"
+    /// name = "firmware build"
+    /// format = "ascii:16"
+    /// access = "read"
+    /// ```
+    pub fn load_toml(&mut self, s: &str) -> Result<(), Error> {
+        let defs: ItemDefs = toml::from_str(s)?;
+        self.load(defs)
+    }
+    /// 从 JSON 加载数据项定义，结构与 TOML 相同：`{"item": [...]}`
+    pub fn load_json(&mut self, s: &str) -> Result<(), Error> {
+        let defs: ItemDefs = serde_json::from_str(s)?;
+        self.load(defs)
+    }
+    /// 按扩展名（.toml / .json）加载数据项定义文件
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => self.load_toml(&s),
+            Some("json") => self.load_json(&s),
+            _ => Err(format!("unsupported definition file `{}`", path.display()).into()),
+        }
+    }
+    fn load(&mut self, defs: ItemDefs) -> Result<(), Error> {
+        let mut catalog = self.clone();
+        for def in defs.item {
+            catalog.register(DataItem::try_from(def)?)?;
+        }
+        *self = catalog;
+        Ok(())
+    }
+    /// 解码单个数据项，bytes 为应答数据域中数据标识之后的部分
+    pub fn decode(&self, id: DataId, bytes: &[u8]) -> Result<Value, Error> {
+        match self.lookup(id) {
+            Some(item) => item.decode(bytes),
+            None => Err(format!("unknown data id `{}`", id).into()),
+        }
+    }
+    /// 数据块包含的数据项，按 FFH 所在字节从小到大依次排列
+    pub fn block_members(&self, id: DataId) -> Result<Vec<DataItem>, Error> {
+        let index = match id.wildcard() {
+            Some(i) => i,
+            None => return Err(format!("`{}` is not a block data id", id).into()),
+        };
+        // 从第一个存在的数据项开始，到第一个不存在的数据项为止
+        let items = (0..0xff_u8)
+            .map(|v| self.lookup(id.with_byte(index, v)))
+            .skip_while(|item| item.is_none())
+            .take_while(|item| item.is_some())
+            .flatten()
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            return Err(format!("unknown block data id `{}`", id).into());
+        }
+        Ok(items)
+    }
+    /// 将数据块应答拆分为各数据项
+    pub fn expand_block(&self, id: DataId, bytes: &[u8]) -> Result<BTreeMap<DataId, Value>, Error> {
+        let mut values = BTreeMap::new();
+        let mut rest = bytes;
+        for item in self.block_members(id)? {
+            if rest.is_empty() {
+                break;
+            }
+            if rest.len() < item.len() {
+                return Err(format!(
+                    "truncated block `{}` at `{}`: expect {} bytes, got {}",
+                    id,
+                    item.id,
+                    item.len(),
+                    rest.len()
+                )
+                .into());
+            }
+            let (head, tail) = rest.split_at(item.len());
+            values.insert(item.id, item.decode(head)?);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(format!(
+                "block `{}` has {} unexpected trailing bytes",
+                id,
+                rest.len()
+            )
+            .into());
+        }
+        Ok(values)
+    }
+    /// 按目录解析读数据应答帧，用于日志和调试输出
+    pub fn dissect(&self, pdu: &ProtocolDataUnit) -> String {
+        let mut s = format!("{} c={:02x}", pdu.address_real_str(), pdu.c());
+        let payload = pdu.payload();
+        if pdu.is_abnormal() {
+            s.push_str(&format!(" {}", Exception::from_payload(&payload)));
+            return s;
+        }
+        let id = match DataId::from_wire(&payload) {
+            Ok(id) if pdu.is_response() && pdu.function() == 0x11 => id,
+            _ => {
+                s.push_str(&format!(" data={}", hex::encode(&payload)));
+                return s;
+            }
+        };
+        let data = &payload[4..];
+        let values = if id.is_block() {
+            self.expand_block(id, data)
+        } else {
+            self.decode(id, data).map(|v| BTreeMap::from([(id, v)]))
+        };
+        match values {
+            Ok(values) => {
+                for (id, value) in values {
+                    // lookup 必然成功，decode 已经找到该数据项
+                    let item = self.lookup(id).unwrap();
+                    s.push_str(&format!(" {} {}={}", id, item.name, value));
+                    if let Some(unit) = item.unit {
+                        s.push_str(&unit);
+                    }
+                }
+            }
+            Err(e) => s.push_str(&format!(" {} data={} ({})", id, hex::encode(data), e)),
+        }
+        s
+    }
+}
+
+const ENERGY_TYPES: [(&str, &str); 11] = [
+    ("组合有功", "kWh"),
+    ("正向有功", "kWh"),
+    ("反向有功", "kWh"),
+    ("组合无功1", "kvarh"),
+    ("组合无功2", "kvarh"),
+    ("第一象限无功", "kvarh"),
+    ("第二象限无功", "kvarh"),
+    ("第三象限无功", "kvarh"),
+    ("第四象限无功", "kvarh"),
+    ("正向视在", "kVAh"),
+    ("反向视在", "kVAh"),
+];
+
+const PHASES: [&str; 4] = ["总", "A相", "B相", "C相"];
+
+fn tariff_name(di1: u8) -> String {
+    if di1 == 0 {
+        "总".to_string()
+    } else {
+        format!("费率{}", di1)
+    }
+}
+
+fn settlement_name(di0: u8) -> String {
+    if di0 == 0 {
+        "当前".to_string()
+    } else {
+        format!("上{}结算日", di0)
+    }
+}
+
+fn item(
+    id: DataId,
+    name: String,
+    format: &str,
+    unit: Option<&str>,
+    access: Access,
+) -> Option<DataItem> {
+    Some(DataItem {
+        id,
+        name,
+        format: DataFormat::parse(format).ok()?,
+        unit: unit.map(|u| u.to_string()),
+        access,
+    })
+}
+
+/// DL/T 645-2007 附录 A 中的常用数据标识
+fn standard(id: DataId) -> Option<DataItem> {
+    use Access::*;
+    let [di3, di2, di1, di0] = id.bytes();
+    match (di3, di2, di1, di0) {
+        (0x00, 0x00..=0x0A, 0x00..=0x3F, 0x00..=0x0C) => {
+            let (kind, unit) = ENERGY_TYPES[di2 as usize];
+            // 组合电能可能为负
+            let format = if matches!(di2, 0x00 | 0x03 | 0x04) {
+                "±XXXXXX.XX"
+            } else {
+                "XXXXXX.XX"
+            };
+            let name = format!("({}){}{}电能", settlement_name(di0), kind, tariff_name(di1));
+            item(id, name, format, Some(unit), Read)
+        }
+        (0x01, 0x01..=0x0A, 0x00..=0x3F, 0x00..=0x0C) => {
+            let (kind, unit) = ENERGY_TYPES[di2 as usize];
+            let unit = unit.trim_end_matches('h');
+            let name = format!(
+                "({}){}{}最大需量及发生时间",
+                settlement_name(di0),
+                kind,
+                tariff_name(di1)
+            );
+            item(id, name, "XX.XXXX YYMMDDhhmm", Some(unit), Read)
+        }
+        (0x02, 0x01, 0x01..=0x03, 0x00) => item(
+            id,
+            format!("{}电压", PHASES[di1 as usize]),
+            "XXX.X",
+            Some("V"),
+            Read,
+        ),
+        (0x02, 0x02, 0x01..=0x03, 0x00) => item(
+            id,
+            format!("{}电流", PHASES[di1 as usize]),
+            "±XXX.XXX",
+            Some("A"),
+            Read,
+        ),
+        (0x02, 0x03..=0x05, 0x00..=0x03, 0x00) => {
+            let (kind, unit) = [
+                ("有功功率", "kW"),
+                ("无功功率", "kvar"),
+                ("视在功率", "kVA"),
+            ][di2 as usize - 3];
+            item(
+                id,
+                format!("瞬时{}{}", PHASES[di1 as usize], kind),
+                "±XX.XXXX",
+                Some(unit),
+                Read,
+            )
+        }
+        (0x02, 0x06, 0x00..=0x03, 0x00) => item(
+            id,
+            format!("{}功率因数", PHASES[di1 as usize]),
+            "±X.XXX",
+            None,
+            Read,
+        ),
+        (0x02, 0x07, 0x01..=0x03, 0x00) => item(
+            id,
+            format!("{}相角", PHASES[di1 as usize]),
+            "XXX.X",
+            Some("°"),
+            Read,
+        ),
+        (0x02, 0x80, 0x00, 0x01..=0x0A) => {
+            let (name, format, unit) = [
+                ("零线电流", "±XXX.XXX", Some("A")),
+                ("电网频率", "XX.XX", Some("Hz")),
+                ("一分钟有功总平均功率", "XX.XXXX", Some("kW")),
+                ("当前有功需量", "±XX.XXXX", Some("kW")),
+                ("当前无功需量", "±XX.XXXX", Some("kvar")),
+                ("当前视在需量", "±XX.XXXX", Some("kVA")),
+                ("表内温度", "±XXX.X", Some("℃")),
+                ("时钟电池电压(内部)", "XX.XX", Some("V")),
+                ("停电抄表电池电压(外部)", "XX.XX", Some("V")),
+                ("内部电池工作时间", "XXXXXXXX", Some("min")),
+            ][di0 as usize - 1];
+            item(id, name.to_string(), format, unit, Read)
+        }
+        (0x04, 0x00, 0x01, 0x01..=0x07) => {
+            let (name, format, unit) = [
+                ("日期及星期", "YYMMDDWW", None),
+                ("时间", "hhmmss", None),
+                ("最大需量周期", "NN", Some("min")),
+                ("滑差时间", "NN", Some("min")),
+                ("校表脉冲宽度", "XXXX", Some("ms")),
+                ("两套时区表切换时间", "YYMMDDhhmm", None),
+                ("两套日时段表切换时间", "YYMMDDhhmm", None),
+            ][di0 as usize - 1];
+            item(id, name.to_string(), format, unit, ReadWrite)
+        }
+        (0x04, 0x00, 0x02, 0x01..=0x05) => {
+            let (name, format) = [
+                ("年时区数", "NN"),
+                ("日时段表数", "NN"),
+                ("日时段数", "NN"),
+                ("费率数", "NN"),
+                ("公共假日数", "NNNN"),
+            ][di0 as usize - 1];
+            item(id, name.to_string(), format, None, ReadWrite)
+        }
+        (0x04, 0x00, 0x04, 0x01..=0x03) => {
+            let (name, format) = [
+                ("通信地址", "NNNNNNNNNNNN"),
+                ("表号", "NNNNNNNNNNNN"),
+                ("资产管理编码", "ascii:32"),
+            ][di0 as usize - 1];
+            item(id, name.to_string(), format, None, ReadWrite)
+        }
+        (0x04, 0x00, 0x05, 0x01..=0x07) => {
+            item(id, format!("电表运行状态字{}", di0), "raw:2", None, Read)
+        }
+        (0x04, 0x00, 0x07, 0x01..=0x05) => {
+            let name = [
+                "调制型红外光口波特率特征字",
+                "接触式红外光口波特率特征字",
+                "通信口1波特率特征字",
+                "通信口2波特率特征字",
+                "通信口3波特率特征字",
+            ][di0 as usize - 1];
+            item(id, name.to_string(), "raw:1", None, ReadWrite)
+        }
+        (0x04, 0x00, 0x0C, 0x01..=0x0A) => {
+            item(id, format!("{}级密码", di0 - 1), "raw:4", None, Write)
+        }
+        (0x04, 0x01..=0x02, 0x00, 0x00) => item(
+            id,
+            format!("第{}套时区表数据", di2),
+            "MMDDNN*14",
+            None,
+            ReadWrite,
+        ),
+        (0x04, 0x01..=0x02, 0x00, 0x01..=0x08) => item(
+            id,
+            format!("第{}套第{}日时段表数据", di2, di0),
+            "hhmmNN*14",
+            None,
+            ReadWrite,
+        ),
+        (0x04, 0x80, 0x00, 0x01..=0x03) => {
+            let name = ["厂家软件版本号", "厂家硬件版本号", "厂家编号"][di0 as usize - 1];
+            item(id, name.to_string(), "ascii:32", None, Read)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_format() {
+        assert_eq!(
+            DataFormat::parse("XXXXXX.XX").unwrap(),
+            DataFormat::Decimal {
+                len: 4,
+                decimals: 2,
+                signed: false
+            }
+        );
+        assert_eq!(
+            DataFormat::parse("±XXX.XXX").unwrap(),
+            DataFormat::Decimal {
+                len: 3,
+                decimals: 3,
+                signed: true
+            }
+        );
+        assert_eq!(DataFormat::parse("XX.XXXX YYMMDDhhmm").unwrap().len(), 8);
+        assert_eq!(DataFormat::parse("MMDDNN*14").unwrap().len(), 42);
+        assert!(DataFormat::parse("XXX").is_err());
+        assert!(DataFormat::parse("ascii:").is_err());
+    }
+    #[test]
+    fn decode() {
+        let catalog = Catalog::new();
+        // 000123.45 kWh
+        let v = catalog
+            .decode(DataId(0x00010000), &[0x45, 0x23, 0x01, 0x00])
+            .unwrap();
+        assert_eq!(v.to_string(), "123.45");
+        // -1.500 A
+        let v = catalog
+            .decode(DataId(0x02020100), &[0x00, 0x15, 0x80])
+            .unwrap();
+        assert_eq!(v.as_f64(), Some(-1.5));
+        assert!(catalog
+            .decode(DataId(0x00010000), &[0x4a, 0x23, 0x01, 0x00])
+            .is_err());
+        assert!(catalog.decode(DataId(0x0f000000), &[]).is_err());
+    }
+    #[test]
+    fn expand_block() {
+        let catalog = Catalog::new();
+        let id = DataId(0x0201FF00);
+        let values = catalog
+            .expand_block(id, &[0x00, 0x22, 0x01, 0x22, 0x02, 0x22])
+            .unwrap();
+        assert_eq!(
+            values.keys().copied().collect::<Vec<_>>(),
+            vec![DataId(0x02010100), DataId(0x02010200), DataId(0x02010300)]
+        );
+        assert_eq!(values[&DataId(0x02010300)].to_string(), "220.2");
+        // 总 + 2 个费率
+        let values = catalog
+            .expand_block(
+                DataId(0x0001FF00),
+                &[0x00, 0x01, 0, 0, 0x00, 0x02, 0, 0, 0x00, 0x03, 0, 0],
+            )
+            .unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[&DataId(0x00010200)].to_string(), "3.00");
+        assert!(catalog.expand_block(id, &[0x00, 0x22, 0x01]).is_err());
+        assert!(catalog.expand_block(DataId(0x02010100), &[]).is_err());
+    }
+    #[test]
+    fn register() {
+        let mut catalog = Catalog::new();
+        let item = |id: u32, name: &str| DataItem {
+            id: DataId(id),
+            name: name.to_string(),
+            format: DataFormat::parse("XXXX").unwrap(),
+            unit: None,
+            access: Access::Read,
+        };
+        assert!(catalog.register(item(0x00010000, "energy")).is_err());
+        assert!(catalog.register(item(0x04A0FF01, "block")).is_err());
+        catalog.register(item(0x04A00001, "relay")).unwrap();
+        assert!(catalog.register(item(0x04A00001, "relay")).is_err());
+        assert_eq!(catalog.lookup(DataId(0x04A00001)).unwrap().name, "relay");
+        assert!(catalog.unregister(DataId(0x04A00001)).is_some());
+        assert!(catalog.lookup(DataId(0x04A00001)).is_none());
+    }
+    #[test]
+    fn load() {
+        let mut catalog = Catalog::new();
+        catalog
+            .load_toml(
+                r#"
+                [[item]]
+                id = "04a00101"
+                name = "harmonic 2"
+                format = "XX.XX"
+                unit = "%"
+                access = "read"
+
+                [[item]]
+                id = "04a00102"
+                name = "harmonic 3"
+                format = "XX.XX"
+                unit = "%"
+                access = "read"
+                "#,
+            )
+            .unwrap();
+        let values = catalog
+            .expand_block(DataId(0x04A001FF), &[0x12, 0x01, 0x34, 0x02])
+            .unwrap();
+        assert_eq!(values[&DataId(0x04A00102)].to_string(), "2.34");
+        // 冲突时整个文件都不加载
+        let json = r#"{"item": [
+            {"id": "04a00201", "name": "build", "format": "ascii:8", "access": "read"},
+            {"id": "04000401", "name": "address", "format": "NNNNNNNNNNNN", "access": "read_write"}
+        ]}"#;
+        assert!(catalog.load_json(json).is_err());
+        assert!(catalog.lookup(DataId(0x04A00201)).is_none());
+        catalog
+            .load_json(&json.replace("04000401", "04a00202"))
+            .unwrap();
+        assert_eq!(
+            catalog.lookup(DataId(0x04A00202)).unwrap().access,
+            Access::ReadWrite
+        );
+    }
+    #[test]
+    fn dissect() {
+        let mut catalog = Catalog::new();
+        catalog
+            .load_json(r#"{"item": [{"id": "04a00001", "name": "relay", "format": "NN", "access": "read"}]}"#)
+            .unwrap();
+        let pdu = ProtocolDataUnit::from_cmd_2(
+            vec![0x20, 0x22, 0x08, 0x31, 0x00, 0x02],
+            0x91,
+            &vec![vec![0x04, 0xA0, 0x00, 0x01], vec![0x01]],
+        )
+        .unwrap();
+        assert_eq!(catalog.dissect(&pdu), "202208310002 c=91 04a00001 relay=01");
+    }
+}
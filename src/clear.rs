@@ -0,0 +1,177 @@
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::password::{OperatorCode, Password};
+use crate::transporter::Transporter;
+
+/// 事件清零时表示清除全部事件记录的数据标识
+pub const ALL_EVENTS: DataId = DataId(0xFFFFFFFF);
+
+/// 清零对象
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClearTarget {
+    /// 最大需量清零（控制码 19H）
+    Demand,
+    /// 电表清零（控制码 1AH），清空电能量、最大需量、冻结及事件记录等
+    Meter,
+    /// 事件清零（控制码 1BH），ALL_EVENTS 清除全部事件，其余清除指定事件记录
+    Events(DataId),
+}
+
+impl ClearTarget {
+    pub fn control_code(&self) -> u8 {
+        match self {
+            Self::Demand => 0x19,
+            Self::Meter => 0x1A,
+            Self::Events(_) => 0x1B,
+        }
+    }
+}
+
+/// 清零确认，必须针对目标电表显式创建，避免误发清零命令
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClearConfirmation {
+    target: ClearTarget,
+    addr: MeterAddress,
+}
+
+impl ClearConfirmation {
+    /// 确认对 addr 执行 target 清零
+    pub fn confirm(target: ClearTarget, addr: &MeterAddress) -> Self {
+        Self {
+            target,
+            addr: *addr,
+        }
+    }
+}
+
+/// 清零命令
+#[derive(Clone, Debug)]
+pub struct Clear {
+    pub target: ClearTarget,
+    pub password: Password,
+    pub operator: OperatorCode,
+}
+
+impl Clear {
+    pub fn new(target: ClearTarget, password: Password, operator: OperatorCode) -> Self {
+        Self {
+            target,
+            password,
+            operator,
+        }
+    }
+    pub fn to_pdu(&self, addr: &MeterAddress) -> Result<ProtocolDataUnit, Error> {
+        let mut segments = vec![self.password.segment(), self.operator.segment()];
+        if let ClearTarget::Events(di) = self.target {
+            segments.push(di.bytes().to_vec());
+        }
+        ProtocolDataUnit::from_cmd_2(addr.to_vec(), self.target.control_code(), &segments)
+    }
+    /// 正常应答 99H/9AH/9BH，异常应答 D9H/DAH/DBH
+    pub fn check_reply(&self, reply: &ProtocolDataUnit) -> Result<(), Error> {
+        reply.check_reply(self.target.control_code())
+    }
+}
+
+/// 清零，confirmation 必须与请求的清零对象和电表地址一致
+pub async fn clear<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    request: &Clear,
+    confirmation: ClearConfirmation,
+) -> Result<(), Error> {
+    if confirmation != ClearConfirmation::confirm(request.target, addr) {
+        return Err(format!(
+            "clear {:?} of `{}` is not confirmed, confirmation is for {:?} of `{}`",
+            request.target, addr, confirmation.target, confirmation.addr
+        )
+        .into());
+    }
+    if addr.is_broadcast() || addr.is_wildcard() {
+        return Err(format!("can not clear meter by address `{}`", addr).into());
+    }
+    let adu: Vec<u8> = request.to_pdu(addr)?.into();
+    match transporter.send(&adu).await? {
+        Some(reply) => request.check_reply(&reply),
+        None => Err(format!("no response clearing {:?} of `{}`", request.target, addr).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::error::Exception;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    fn request(target: ClearTarget) -> Clear {
+        Clear::new(
+            target,
+            "02123456".parse().unwrap(),
+            "01020304".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn to_pdu() {
+        let addr = ADDR.parse().unwrap();
+        let pdu = request(ClearTarget::Demand).to_pdu(&addr).unwrap();
+        assert_eq!(pdu.c(), 0x19);
+        assert_eq!(hex::encode(pdu.payload()), "0256341204030201");
+        let pdu = request(ClearTarget::Events(ALL_EVENTS))
+            .to_pdu(&addr)
+            .unwrap();
+        assert_eq!(pdu.c(), 0x1B);
+        assert_eq!(hex::encode(pdu.payload()), "0256341204030201ffffffff");
+    }
+    #[test]
+    fn confirmation() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x9A, &[]).reply(ADDR, 0xDB, &[vec![0x04]]);
+            let addr = ADDR.parse().unwrap();
+            let other = "202208310003".parse().unwrap();
+            let meter = request(ClearTarget::Meter);
+            assert!(clear(
+                &mut t,
+                &addr,
+                &meter,
+                ClearConfirmation::confirm(ClearTarget::Meter, &other)
+            )
+            .await
+            .is_err());
+            assert!(clear(
+                &mut t,
+                &addr,
+                &meter,
+                ClearConfirmation::confirm(ClearTarget::Demand, &addr)
+            )
+            .await
+            .is_err());
+            assert!(t.sent.is_empty());
+            clear(
+                &mut t,
+                &addr,
+                &meter,
+                ClearConfirmation::confirm(ClearTarget::Meter, &addr),
+            )
+            .await
+            .unwrap();
+            let target = ClearTarget::Events(ALL_EVENTS);
+            let e = clear(
+                &mut t,
+                &addr,
+                &request(target),
+                ClearConfirmation::confirm(target, &addr),
+            )
+            .await
+            .unwrap_err();
+            assert!(e.downcast_ref::<Exception>().unwrap().unauthorized());
+        })
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+
+use crate::address::{read_address, write_address, MeterAddress};
+use crate::baud::{change_baud_rate, BaudRate, BaudRateControl};
+use crate::broadcast::broadcast_time;
+use crate::catalog::{Catalog, DataId, Value};
+use crate::clear::{clear, Clear, ClearConfirmation};
+use crate::datetime::DateTime;
+use crate::discovery::{discover, Discovery, DiscoveryProgress, DiscoveryReport};
+use crate::error::ClientError;
+use crate::freeze::{freeze, FreezeTime};
+use crate::output::{set_output, OutputMode};
+use crate::password::{change_password, ChangePassword, OperatorCode, Password};
+use crate::probe::{probe, MeterProfile, ProbeSet};
+use crate::read::{read_block_with_max_frames, read_with_max_frames, DEFAULT_MAX_FRAMES};
+use crate::remote::{read_relay_status, remote_control, CommandCipher, RelayStatus, RemoteControl};
+use crate::retry::{Retry, RetryPolicy};
+use crate::security::{KeyProvider, SecuritySession};
+use crate::tariff::{read_schedule, write_schedule, ScheduleSet, TariffSchedule};
+use crate::transporter::Transporter;
+use crate::write::{write, Write};
+
+/// DL/T645-2007 客户端，封装各命令的组帧、后续帧、异常应答及应答校验，
+/// 各操作返回按类型区分的 ClientError。
+/// 各操作按默认重试策略执行，需临时使用其他策略时用 override_policy。
+pub struct Dlt645Client<T: Transporter> {
+    transporter: Retry<T>,
+    catalog: Catalog,
+    max_frames: usize,
+}
+
+impl<T: Transporter + Send> Dlt645Client<T> {
+    pub fn new(transporter: T) -> Self {
+        Self {
+            transporter: Retry::new(transporter, RetryPolicy::default()),
+            catalog: Catalog::new(),
+            max_frames: DEFAULT_MAX_FRAMES,
+        }
+    }
+    pub fn with_catalog(mut self, catalog: Catalog) -> Self {
+        self.catalog = catalog;
+        self
+    }
+    /// 读数据时最多接收的帧数（含后续帧）
+    pub fn with_max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = max_frames;
+        self
+    }
+    /// 各命令默认的重试策略
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.transporter.set_policy(policy);
+        self
+    }
+    /// 替换默认重试策略，返回原策略
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) -> RetryPolicy {
+        self.transporter.set_policy(policy)
+    }
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        self.transporter.policy()
+    }
+    /// 返回按 policy 重试的客户端，释放后恢复原策略
+    pub fn override_policy(&mut self, policy: RetryPolicy) -> PolicyScope<'_, T> {
+        let default = Some(self.set_retry_policy(policy));
+        PolicyScope {
+            client: self,
+            default,
+        }
+    }
+    /// 最近一次操作的发送次数（含重试及后续帧）
+    pub fn attempts(&self) -> u32 {
+        self.transporter.attempts()
+    }
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+    pub fn catalog_mut(&mut self) -> &mut Catalog {
+        &mut self.catalog
+    }
+    pub fn transporter(&mut self) -> &mut T {
+        self.transporter.inner()
+    }
+    pub fn into_inner(self) -> T {
+        self.transporter.into_inner()
+    }
+    pub async fn open(&mut self) -> Result<(), ClientError> {
+        Ok(self.transporter.open().await?)
+    }
+    pub async fn close(&mut self) -> Result<(), ClientError> {
+        Ok(self.transporter.close().await?)
+    }
+    /// 丢弃已收到但尚未读取的数据，返回丢弃的字节数
+    pub async fn flush_input(&mut self) -> Result<usize, ClientError> {
+        Ok(self.transporter.flush_input().await?)
+    }
+    /// 读数据，返回数据标识之后的原始数据（低字节在前）
+    pub async fn read_raw(
+        &mut self,
+        addr: &MeterAddress,
+        di: DataId,
+    ) -> Result<Vec<u8>, ClientError> {
+        self.transporter.reset();
+        Ok(read_with_max_frames(&mut self.transporter, addr, di, self.max_frames).await?)
+    }
+    /// 读数据并按目录解码
+    pub async fn read(&mut self, addr: &MeterAddress, di: DataId) -> Result<Value, ClientError> {
+        let data = self.read_raw(addr, di).await?;
+        Ok(self.catalog.decode(di, &data)?)
+    }
+    /// 读数据块并拆分为各数据项
+    pub async fn read_block(
+        &mut self,
+        addr: &MeterAddress,
+        di: DataId,
+    ) -> Result<BTreeMap<DataId, Value>, ClientError> {
+        self.transporter.reset();
+        Ok(read_block_with_max_frames(
+            &mut self.transporter,
+            &self.catalog,
+            addr,
+            di,
+            self.max_frames,
+        )
+        .await?)
+    }
+    /// 按目录探测电表支持的数据标识、协议版本等能力
+    pub async fn probe(
+        &mut self,
+        addr: &MeterAddress,
+        set: &ProbeSet,
+    ) -> Result<MeterProfile, ClientError> {
+        self.transporter.reset();
+        Ok(probe(&mut self.transporter, &self.catalog, addr, set).await?)
+    }
+    pub async fn write(&mut self, addr: &MeterAddress, request: &Write) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(write(&mut self.transporter, addr, request).await?)
+    }
+    /// 读总线上唯一电表的通信地址
+    pub async fn read_address(&mut self) -> Result<MeterAddress, ClientError> {
+        self.transporter.reset();
+        Ok(read_address(&mut self.transporter).await?)
+    }
+    /// 写总线上唯一电表的通信地址
+    pub async fn write_address(&mut self, new: MeterAddress) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(write_address(&mut self.transporter, new).await?)
+    }
+    /// 用缩位地址搜索总线上的所有电表
+    pub async fn discover<F: FnMut(&DiscoveryProgress)>(
+        &mut self,
+        discovery: &Discovery,
+        progress: F,
+    ) -> Result<DiscoveryReport, ClientError> {
+        self.transporter.reset();
+        Ok(discover(&mut self.transporter, discovery, progress).await?)
+    }
+    /// 广播校时，返回写入的字节数
+    pub async fn broadcast_time(
+        &mut self,
+        now: &DateTime,
+        reference: Option<&DateTime>,
+    ) -> Result<usize, ClientError> {
+        self.transporter.reset();
+        Ok(broadcast_time(&mut self.transporter, now, reference).await?)
+    }
+    pub async fn freeze(
+        &mut self,
+        addr: &MeterAddress,
+        time: FreezeTime,
+    ) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(freeze(&mut self.transporter, addr, time).await?)
+    }
+    pub async fn change_password(
+        &mut self,
+        addr: &MeterAddress,
+        request: &ChangePassword,
+    ) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(change_password(&mut self.transporter, addr, request).await?)
+    }
+    pub async fn clear(
+        &mut self,
+        addr: &MeterAddress,
+        request: &Clear,
+        confirmation: ClearConfirmation,
+    ) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(clear(&mut self.transporter, addr, request, confirmation).await?)
+    }
+    /// 远程控制并读运行状态字 3 确认，直到电表已执行或超过 request.confirm_timeout
+    pub async fn remote_control<C: CommandCipher + ?Sized>(
+        &mut self,
+        addr: &MeterAddress,
+        request: &RemoteControl,
+        cipher: &C,
+    ) -> Result<RelayStatus, ClientError> {
+        self.transporter.reset();
+        Ok(remote_control(&mut self.transporter, addr, request, cipher).await?)
+    }
+    pub async fn read_relay_status(
+        &mut self,
+        addr: &MeterAddress,
+    ) -> Result<RelayStatus, ClientError> {
+        self.transporter.reset();
+        Ok(read_relay_status(&mut self.transporter, addr).await?)
+    }
+    pub async fn set_output(
+        &mut self,
+        addr: &MeterAddress,
+        mode: OutputMode,
+    ) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(set_output(&mut self.transporter, addr, mode).await?)
+    }
+    /// 身份认证
+    pub async fn authenticate<K: KeyProvider>(
+        &mut self,
+        session: &mut SecuritySession<K>,
+    ) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(session.authenticate(&mut self.transporter).await?)
+    }
+    pub async fn read_schedule(
+        &mut self,
+        addr: &MeterAddress,
+        set: ScheduleSet,
+    ) -> Result<TariffSchedule, ClientError> {
+        self.transporter.reset();
+        Ok(read_schedule(&mut self.transporter, addr, set).await?)
+    }
+    pub async fn write_schedule(
+        &mut self,
+        addr: &MeterAddress,
+        set: ScheduleSet,
+        schedule: &TariffSchedule,
+        switch_time: &DateTime,
+        password: &Password,
+        operator: &OperatorCode,
+    ) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(write_schedule(
+            &mut self.transporter,
+            addr,
+            set,
+            schedule,
+            switch_time,
+            password,
+            operator,
+        )
+        .await?)
+    }
+}
+
+impl<T: BaudRateControl + Send> Dlt645Client<T> {
+    /// 更改通信速率并按新速率重新打开串口
+    pub async fn change_baud_rate(
+        &mut self,
+        addr: &MeterAddress,
+        rate: BaudRate,
+    ) -> Result<(), ClientError> {
+        self.transporter.reset();
+        Ok(change_baud_rate(&mut self.transporter, addr, rate).await?)
+    }
+}
+
+/// 临时使用其他重试策略的客户端，由 Dlt645Client::override_policy 获得。
+/// 释放时恢复原策略，操作的 Future 被取消（如超时、select!）时同样恢复
+pub struct PolicyScope<'a, T: Transporter + Send> {
+    client: &'a mut Dlt645Client<T>,
+    default: Option<RetryPolicy>,
+}
+
+impl<T: Transporter + Send> Deref for PolicyScope<'_, T> {
+    type Target = Dlt645Client<T>;
+    fn deref(&self) -> &Self::Target {
+        self.client
+    }
+}
+
+impl<T: Transporter + Send> DerefMut for PolicyScope<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client
+    }
+}
+
+impl<T: Transporter + Send> Drop for PolicyScope<'_, T> {
+    fn drop(&mut self) {
+        if let Some(default) = self.default.take() {
+            self.client.set_retry_policy(default);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use std::time::Duration;
+
+    use super::*;
+    use crate::mock::{timeout, MockTransporter};
+    use crate::retry::Backoff;
+
+    const ADDR: &str = "202208310002";
+
+    #[test]
+    fn client() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x93, &[hex::decode(ADDR).unwrap()])
+                .reply(
+                    ADDR,
+                    0xB1,
+                    &[vec![0x00, 0x01, 0x00, 0x00], vec![0x00, 0x00, 0x12, 0x34]],
+                )
+                .reply(ADDR, 0x92, &[vec![0x00, 0x01, 0x00, 0x00], vec![0x01]])
+                .reply(ADDR, 0xD1, &[vec![0x02]]);
+            let mut client = Dlt645Client::new(t).with_max_frames(1);
+            let addr = client.read_address().await.unwrap();
+            // 超出最大帧数
+            assert!(client.read(&addr, DataId(0x00010000)).await.is_err());
+            client.transporter().replies.clear();
+            client
+                .transporter()
+                .reply(
+                    ADDR,
+                    0x91,
+                    &[vec![0x00, 0x01, 0x00, 0x00], vec![0x00, 0x00, 0x12, 0x34]],
+                )
+                .reply(ADDR, 0xD1, &[vec![0x02]]);
+            let value = client.read(&addr, DataId(0x00010000)).await.unwrap();
+            assert_eq!(value.to_string(), "12.34");
+            let e = client.read(&addr, DataId(0x00010000)).await.unwrap_err();
+            assert!(matches!(e, ClientError::Exception(e) if e.no_data()));
+            assert_eq!(client.attempts(), 1);
+        })
+    }
+    #[test]
+    fn override_policy() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.replies.push_back(Err(timeout()));
+            t.reply(
+                ADDR,
+                0x91,
+                &[vec![0x00, 0x01, 0x00, 0x00], vec![0x00, 0x00, 0x12, 0x34]],
+            );
+            t.replies.push_back(Err(timeout()));
+            let mut client = Dlt645Client::new(t);
+            let addr = ADDR.parse().unwrap();
+            let mut scope = client.override_policy(RetryPolicy::new(3));
+            let value = scope.read(&addr, DataId(0x00010000)).await.unwrap();
+            assert_eq!(scope.attempts(), 2);
+            assert_eq!(value.to_string(), "12.34");
+            drop(scope);
+            // 默认策略不重试
+            assert_eq!(client.retry_policy(), &RetryPolicy::default());
+            let e = client.read(&addr, DataId(0x00010000)).await.unwrap_err();
+            assert!(matches!(e, ClientError::Timeout(_)));
+            assert_eq!(client.attempts(), 1);
+        })
+    }
+    #[test]
+    fn override_policy_cancelled() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.replies.push_back(Err(timeout()));
+            let mut client = Dlt645Client::new(t);
+            let addr = ADDR.parse().unwrap();
+            let policy =
+                RetryPolicy::new(3).with_backoff(Backoff::Fixed(Duration::from_secs(3600)));
+            // 重试等待中被取消，仍恢复原策略
+            let r = tokio::time::timeout(
+                Duration::from_millis(10),
+                client
+                    .override_policy(policy)
+                    .read(&addr, DataId(0x00010000)),
+            )
+            .await;
+            assert!(r.is_err());
+            assert_eq!(client.retry_policy(), &RetryPolicy::default());
+        })
+    }
+    #[test]
+    fn retry_commands() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.replies.push_back(Err(timeout()));
+            t.replies.push_back(Err(timeout()));
+            t.reply(ADDR, 0x94, &[]);
+            let mut client = Dlt645Client::new(t).with_retry_policy(RetryPolicy::new(2));
+            let addr = ADDR.parse().unwrap();
+            let request = Write::new(
+                DataId(0x04000103),
+                "02123456".parse().unwrap(),
+                "01020304".parse().unwrap(),
+                vec![vec![0x15]],
+            );
+            // 写命令默认不重试
+            let e = client.write(&addr, &request).await.unwrap_err();
+            assert!(matches!(e, ClientError::Timeout(_)));
+            assert_eq!(client.attempts(), 1);
+            let policy = RetryPolicy::new(2).with_retry_commands(true);
+            let mut scope = client.override_policy(policy);
+            scope.write(&addr, &request).await.unwrap();
+            assert_eq!(scope.attempts(), 2);
+        })
+    }
+}
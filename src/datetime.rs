@@ -0,0 +1,162 @@
+use std::fmt;
+
+use crate::error::Error;
+
+/// 电表日期时间，年份为 2000~2099
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<Self, Error> {
+        let dt = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        if !(2000..=2099).contains(&year)
+            || !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(format!("invalid date time `{}`", dt).into());
+        }
+        Ok(dt)
+    }
+    /// 自 1970-01-01 00:00:00 起的秒数，不考虑时区
+    pub fn timestamp(&self) -> i64 {
+        let (y, m, d) = (self.year as i64, self.month as i64, self.day as i64);
+        // 以 3 月为一年的开始计算天数
+        let (y, m) = if m <= 2 { (y - 1, m + 9) } else { (y, m - 3) };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let doy = (153 * m + 2) / 5 + d - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+    }
+    pub fn from_timestamp(ts: i64) -> Result<Self, Error> {
+        let days = ts.div_euclid(86400) + 719468;
+        let secs = ts.rem_euclid(86400);
+        let era = days.div_euclid(146097);
+        let doe = days - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Self::new(
+            year as u16,
+            month as u8,
+            day as u8,
+            (secs / 3600) as u8,
+            (secs % 3600 / 60) as u8,
+            (secs % 60) as u8,
+        )
+    }
+    /// 星期，0 为星期日
+    pub fn weekday(&self) -> u8 {
+        // 1970-01-01 为星期四
+        ((self.timestamp().div_euclid(86400) + 4).rem_euclid(7)) as u8
+    }
+    /// YYMMDDhhmm，高字节在前
+    pub fn to_yymmddhhmm(&self) -> Vec<u8> {
+        vec![
+            to_bcd((self.year % 100) as u8),
+            to_bcd(self.month),
+            to_bcd(self.day),
+            to_bcd(self.hour),
+            to_bcd(self.minute),
+        ]
+    }
+    /// 从 YYMMDDhhmm（已减 33H，低字节在前）解析
+    pub fn from_yymmddhhmm(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 5 {
+            return Err(format!("invalid YYMMDDhhmm `{}`", hex::encode(bytes)).into());
+        }
+        Self::new(
+            2000 + from_bcd(bytes[4])? as u16,
+            from_bcd(bytes[3])?,
+            from_bcd(bytes[2])?,
+            from_bcd(bytes[1])?,
+            from_bcd(bytes[0])?,
+            0,
+        )
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+pub(crate) fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        _ => 31,
+    }
+}
+
+pub(crate) fn to_bcd(v: u8) -> u8 {
+    ((v / 10) << 4) | (v % 10)
+}
+
+pub(crate) fn from_bcd(b: u8) -> Result<u8, Error> {
+    if b >> 4 > 9 || b & 0x0f > 9 {
+        return Err(format!("invalid bcd `{:02x}`", b).into());
+    }
+    Ok((b >> 4) * 10 + (b & 0x0f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp() {
+        let dt = DateTime::new(2023, 2, 15, 10, 20, 30).unwrap();
+        assert_eq!(dt.timestamp(), 1676456430);
+        assert_eq!(DateTime::from_timestamp(1676456430).unwrap(), dt);
+        assert_eq!(dt.weekday(), 3);
+        assert!(DateTime::new(2023, 2, 29, 0, 0, 0).is_err());
+        assert!(DateTime::new(2024, 2, 29, 0, 0, 0).is_ok());
+    }
+    #[test]
+    fn yymmddhhmm() {
+        let dt = DateTime::new(2023, 2, 15, 10, 20, 0).unwrap();
+        assert_eq!(dt.to_yymmddhhmm(), vec![0x23, 0x02, 0x15, 0x10, 0x20]);
+        assert_eq!(
+            DateTime::from_yymmddhhmm(&[0x20, 0x10, 0x15, 0x02, 0x23]).unwrap(),
+            dt
+        );
+    }
+}
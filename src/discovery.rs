@@ -0,0 +1,358 @@
+use std::time::{Duration, Instant};
+
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::retry::ErrorKind;
+use crate::transporter::Transporter;
+
+/// 通信地址的数据标识，应答数据为电表地址
+pub const ADDRESS_DI: DataId = DataId(0x04000401);
+
+/// 收到应答后继续等待其他电表应答的默认时间，电表应答延时一般为 20~500ms
+pub const DEFAULT_LISTEN: Duration = Duration::from_millis(500);
+
+/// 搜表设置
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Discovery {
+    /// 搜表总用时，超出后停止并返回已找到的电表
+    pub budget: Option<Duration>,
+    /// 收到有效应答后继续等待的时间，期间收到其他数据视为多块电表应答
+    pub listen: Duration,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        Self {
+            budget: None,
+            listen: DEFAULT_LISTEN,
+        }
+    }
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+    pub fn with_listen(mut self, listen: Duration) -> Self {
+        self.listen = listen;
+        self
+    }
+}
+
+/// 搜表进度，每发送一次探测命令报告一次
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiscoveryProgress {
+    /// 本次探测的缩位地址
+    pub pattern: MeterAddress,
+    pub probes: u32,
+    pub found: usize,
+    /// 待探测的缩位地址数
+    pub pending: usize,
+    pub elapsed: Duration,
+}
+
+/// 搜表结果
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiscoveryReport {
+    pub meters: Vec<MeterAddress>,
+    /// 完整地址仍有冲突，总线上有多块电表地址相同
+    pub duplicates: Vec<MeterAddress>,
+    pub probes: u32,
+    /// 为 false 表示超出时间限制，未搜索完整个地址空间
+    pub complete: bool,
+}
+
+enum Probe {
+    None,
+    Found(MeterAddress),
+    Collision,
+}
+
+/// 向缩位地址读通信地址，判断匹配的电表数
+async fn probe<T: Transporter + Send + ?Sized>(
+    transporter: &mut T,
+    pattern: &MeterAddress,
+    listen: Duration,
+) -> Result<Probe, Error> {
+    let adu: Vec<u8> =
+        ProtocolDataUnit::from_cmd_2(pattern.to_vec(), 0x11, &vec![ADDRESS_DI.bytes().to_vec()])?
+            .into();
+    // 丢弃上次探测中其他电表迟到的应答，避免本次无法解码而误判为冲突
+    transporter.flush_input().await?;
+    let reply = match transporter.send(&adu).await {
+        Ok(Some(reply)) => reply,
+        Ok(None) => return Ok(Probe::None),
+        // 超时说明没有电表应答，校验码错误或帧无法解码说明多块电表同时应答，
+        // 其余错误（串口未打开、读写失败等）中止搜表
+        Err(e) => match ErrorKind::of(&e) {
+            ErrorKind::Timeout => return Ok(Probe::None),
+            ErrorKind::Checksum | ErrorKind::Decode => return Ok(Probe::Collision),
+            _ => return Err(e),
+        },
+    };
+    let header = match MeterAddress::from_wire(&reply.address()) {
+        Ok(header) => header,
+        Err(_) => return Ok(Probe::Collision),
+    };
+    let matched = header
+        .bytes()
+        .iter()
+        .zip(pattern.bytes())
+        .all(|(a, p)| p == 0xAA || *a == p);
+    if header.is_wildcard() || !matched {
+        return Ok(Probe::Collision);
+    }
+    // 异常应答同样说明只有一块电表，正常应答的数据域地址须与帧头一致
+    if !reply.is_abnormal() {
+        let payload = reply.payload();
+        let data = payload
+            .get(4..)
+            .and_then(|data| MeterAddress::from_wire(data).ok());
+        if data != Some(header) {
+            return Ok(Probe::Collision);
+        }
+    }
+    // 各电表应答延时不同时，多块电表的应答可能先后完整到达
+    tokio::time::sleep(listen).await;
+    if transporter.flush_input().await? > 0 {
+        return Ok(Probe::Collision);
+    }
+    Ok(Probe::Found(header))
+}
+
+/// 搜索总线上所有电表的通信地址。
+/// 先向通配地址读通信地址，多块电表同时应答导致冲突时，从低字节起逐字节
+/// 固定 00~99，其余字节为 AAH 的缩位地址继续探测，直到每个缩位地址只有一块电表应答。
+/// 收到有效应答后再等待 discovery.listen，期间收到其他数据同样视为冲突。
+/// 每次探测后调用 progress 报告进度。超时和冲突以外的通信错误直接返回。
+pub async fn discover<T, F>(
+    transporter: &mut T,
+    discovery: &Discovery,
+    mut progress: F,
+) -> Result<DiscoveryReport, Error>
+where
+    T: Transporter + Send + ?Sized,
+    F: FnMut(&DiscoveryProgress),
+{
+    let start = Instant::now();
+    let mut report = DiscoveryReport::default();
+    let mut pending = vec![MeterAddress::wildcard()];
+    while let Some(pattern) = pending.pop() {
+        if discovery.budget.is_some_and(|b| start.elapsed() >= b) {
+            pending.push(pattern);
+            break;
+        }
+        report.probes += 1;
+        match probe(transporter, &pattern, discovery.listen).await? {
+            Probe::None => {}
+            Probe::Found(addr) => {
+                if !report.meters.contains(&addr) {
+                    report.meters.push(addr);
+                }
+            }
+            Probe::Collision => {
+                let bytes = pattern.bytes();
+                match bytes.iter().rposition(|b| *b == 0xAA) {
+                    Some(i) => {
+                        for d in (0..100u8).rev() {
+                            let mut next = bytes;
+                            next[i] = ((d / 10) << 4) | (d % 10);
+                            pending.push(MeterAddress::new(next));
+                        }
+                    }
+                    None => {
+                        if !report.duplicates.contains(&pattern) {
+                            report.duplicates.push(pattern);
+                        }
+                    }
+                }
+            }
+        }
+        progress(&DiscoveryProgress {
+            pattern,
+            probes: report.probes,
+            found: report.meters.len(),
+            pending: pending.len(),
+            elapsed: start.elapsed(),
+        });
+    }
+    report.complete = pending.is_empty();
+    report.meters.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use async_trait::async_trait;
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::frame::{ChecksumError, DecodeError};
+    use crate::mock::{timeout, MockTransporter};
+
+    /// 模拟总线：匹配的电表都应答。多块电表应答时帧冲突，冲突的残余字节留在接收缓冲区；
+    /// staggered 时各电表先后应答，第一帧完整收到，其余帧留在接收缓冲区
+    struct Bus {
+        meters: Vec<MeterAddress>,
+        staggered: bool,
+        input: Vec<u8>,
+    }
+
+    impl Bus {
+        fn new(meters: Vec<MeterAddress>) -> Self {
+            Self {
+                meters,
+                staggered: false,
+                input: vec![],
+            }
+        }
+    }
+
+    fn address_reply(addr: &MeterAddress) -> ProtocolDataUnit {
+        ProtocolDataUnit::from_cmd_2(
+            addr.to_vec(),
+            0x91,
+            &vec![ADDRESS_DI.bytes().to_vec(), addr.to_vec()],
+        )
+        .unwrap()
+    }
+
+    #[async_trait]
+    impl Transporter for Bus {
+        async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+            let request = ProtocolDataUnit::try_from(adu.to_vec()).map_err(Into::<Error>::into)?;
+            // 缓冲区中的残余字节与应答拼接后无法解码
+            if !self.input.is_empty() {
+                return Err(Box::new(DecodeError("invalid frame".into())));
+            }
+            let pattern = MeterAddress::from_wire(&request.address())?;
+            let matched: Vec<&MeterAddress> = self
+                .meters
+                .iter()
+                .filter(|m| {
+                    m.bytes()
+                        .iter()
+                        .zip(pattern.bytes())
+                        .all(|(a, p)| p == 0xAA || *a == p)
+                })
+                .collect();
+            match matched[..] {
+                [] => Err(timeout()),
+                [addr] => Ok(Some(address_reply(addr))),
+                [first, ref others @ ..] if self.staggered => {
+                    for addr in others {
+                        let adu: Vec<u8> = address_reply(addr).into();
+                        self.input.extend(adu);
+                    }
+                    Ok(Some(address_reply(first)))
+                }
+                _ => {
+                    self.input.extend([0x16, 0xfe]);
+                    Err(Box::new(ChecksumError {
+                        expected: 0x12,
+                        actual: 0x34,
+                    }))
+                }
+            }
+        }
+        async fn flush_input(&mut self) -> Result<usize, Error> {
+            Ok(std::mem::take(&mut self.input).len())
+        }
+        async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+            Ok(adu.len())
+        }
+        async fn open(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+        async fn close(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn discovery() -> Discovery {
+        Discovery::new().with_listen(Duration::ZERO)
+    }
+
+    #[test]
+    fn staggered() {
+        block_on(async {
+            let meters: Vec<MeterAddress> = ["202208310001", "202208310002"]
+                .iter()
+                .map(|a| a.parse().unwrap())
+                .collect();
+            // 两块电表先后完整应答，第二帧同样视为冲突
+            let mut bus = Bus::new(meters.clone());
+            bus.staggered = true;
+            let report = super::discover(&mut bus, &discovery(), |_| {})
+                .await
+                .unwrap();
+            assert_eq!(report.meters, meters);
+            assert_eq!(report.probes, 101);
+        })
+    }
+    #[test]
+    fn discover() {
+        block_on(async {
+            let meters: Vec<MeterAddress> = ["202208310001", "202208310002", "202208310102"]
+                .iter()
+                .map(|a| a.parse().unwrap())
+                .collect();
+            let mut bus = Bus::new(meters.clone());
+            let mut last = None;
+            let report = super::discover(&mut bus, &discovery(), |p| last = Some(*p))
+                .await
+                .unwrap();
+            assert_eq!(report.meters, meters);
+            assert!(report.complete);
+            assert!(report.duplicates.is_empty());
+            // 通配地址 1 次，最低字节 100 次，低字节 02 冲突后次低字节 100 次
+            assert_eq!(report.probes, 201);
+            let last = last.unwrap();
+            assert_eq!(last.probes, 201);
+            assert_eq!(last.found, 3);
+            assert_eq!(last.pending, 0);
+
+            // 单块电表一次探测即可
+            let mut bus = Bus::new(meters[..1].to_vec());
+            let report = super::discover(&mut bus, &discovery(), |_| {})
+                .await
+                .unwrap();
+            assert_eq!(report.meters, meters[..1]);
+            assert_eq!(report.probes, 1);
+
+            // 地址相同的电表
+            let mut bus = Bus::new(vec![meters[0], meters[0]]);
+            let report = super::discover(&mut bus, &discovery(), |_| {})
+                .await
+                .unwrap();
+            assert!(report.meters.is_empty());
+            assert_eq!(report.duplicates, meters[..1]);
+
+            // 通信错误不当作冲突继续搜索
+            let mut t = MockTransporter::new();
+            t.replies
+                .push_back(Err(Box::new(io::Error::from(io::ErrorKind::BrokenPipe))));
+            let e = super::discover(&mut t, &discovery(), |_| {})
+                .await
+                .unwrap_err();
+            assert_eq!(ErrorKind::of(&e), ErrorKind::Io);
+            assert_eq!(t.sent.len(), 1);
+
+            let mut bus = Bus::new(meters);
+            let report =
+                super::discover(&mut bus, &discovery().with_budget(Duration::ZERO), |_| {})
+                    .await
+                    .unwrap();
+            assert!(!report.complete);
+            assert_eq!(report.probes, 0);
+        })
+    }
+}
@@ -1 +1,312 @@
+use std::fmt;
+use std::io;
+
+use crate::address::AddressCollision;
+use crate::frame::{ChecksumError, DecodeError};
+use crate::retry::{ErrorKind, RetriesExhausted};
+use crate::transporter::TimeoutError;
+
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// 异常应答帧（D6=1）数据域中的错误信息字 ERR
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Exception(pub u8);
+
+impl Exception {
+    /// 从异常应答帧中取出错误信息字，数据域为空时视为其他错误
+    pub fn from_payload(payload: &[u8]) -> Self {
+        Self(payload.first().copied().unwrap_or(0x01))
+    }
+    pub fn other(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+    pub fn no_data(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+    pub fn unauthorized(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+    pub fn baud_unchangeable(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+    pub fn year_zones_exceeded(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+    pub fn day_segments_exceeded(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+    pub fn tariffs_exceeded(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reasons = [
+            (self.other(), "other error"),
+            (self.no_data(), "no requested data"),
+            (self.unauthorized(), "password error or unauthorized"),
+            (self.baud_unchangeable(), "baud rate can not be changed"),
+            (self.year_zones_exceeded(), "year time zones exceeded"),
+            (self.day_segments_exceeded(), "daily time segments exceeded"),
+            (self.tariffs_exceeded(), "tariffs exceeded"),
+        ];
+        let reasons = reasons
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, r)| *r)
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "meter exception `{:02x}`: {}",
+            self.0,
+            reasons.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for Exception {}
+
+/// 安全认证异常应答（控制码 C3H）数据域中的安全认证错误信息字 SERR，2 字节
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecurityError(pub u16);
+
+impl SecurityError {
+    /// 从异常应答帧中取出 SERR（低字节在前），数据域为空时视为其他错误
+    pub fn from_payload(payload: &[u8]) -> Self {
+        match payload {
+            [] => Self(0x0001),
+            [low] => Self(*low as u16),
+            [low, high, ..] => Self(u16::from_le_bytes([*low, *high])),
+        }
+    }
+    pub fn other(&self) -> bool {
+        self.0 & 0x0001 != 0
+    }
+    pub fn repeated_recharge(&self) -> bool {
+        self.0 & 0x0002 != 0
+    }
+    pub fn esam_verification_failed(&self) -> bool {
+        self.0 & 0x0004 != 0
+    }
+    pub fn authentication_failed(&self) -> bool {
+        self.0 & 0x0008 != 0
+    }
+    pub fn customer_mismatch(&self) -> bool {
+        self.0 & 0x0010 != 0
+    }
+    pub fn recharge_count_error(&self) -> bool {
+        self.0 & 0x0020 != 0
+    }
+    pub fn purchase_exceeded(&self) -> bool {
+        self.0 & 0x0040 != 0
+    }
+    pub fn address_error(&self) -> bool {
+        self.0 & 0x0080 != 0
+    }
+    pub fn suspended(&self) -> bool {
+        self.0 & 0x0100 != 0
+    }
+}
+
+impl fmt::Display for SecurityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reasons = [
+            (self.other(), "other error"),
+            (self.repeated_recharge(), "repeated recharge"),
+            (self.esam_verification_failed(), "ESAM verification failed"),
+            (self.authentication_failed(), "authentication failed"),
+            (self.customer_mismatch(), "customer number mismatch"),
+            (self.recharge_count_error(), "recharge count error"),
+            (self.purchase_exceeded(), "purchase exceeds limit"),
+            (self.address_error(), "address error"),
+            (self.suspended(), "meter suspended"),
+        ];
+        let reasons = reasons
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, r)| *r)
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "security exception `{:04x}`: {}",
+            self.0,
+            reasons.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for SecurityError {}
+
+/// Dlt645Client 各操作的错误
+#[derive(Debug)]
+pub enum ClientError {
+    /// 发送或接收超时，多为电表无应答
+    Timeout(TimeoutError),
+    /// 应答帧校验码错误
+    Checksum(ChecksumError),
+    /// 接收的字节无法解码为帧
+    Protocol(DecodeError),
+    /// 电表异常应答
+    Exception(Exception),
+    /// 安全认证异常应答
+    Security(SecurityError),
+    /// 多块电表同时应答
+    Collision(AddressCollision),
+    /// 串口、网络读写错误或连接未打开、已关闭
+    Io(io::Error),
+    /// 参数错误、应答与请求不符、数据解码失败等
+    Other(Error),
+}
+
+impl ClientError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Timeout(_) => ErrorKind::Timeout,
+            Self::Checksum(_) => ErrorKind::Checksum,
+            Self::Protocol(_) => ErrorKind::Decode,
+            Self::Exception(_) | Self::Security(_) => ErrorKind::Exception,
+            Self::Io(_) => ErrorKind::Io,
+            Self::Collision(_) | Self::Other(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout(e) => e.fmt(f),
+            Self::Checksum(e) => e.fmt(f),
+            Self::Protocol(e) => e.fmt(f),
+            Self::Exception(e) => e.fmt(f),
+            Self::Security(e) => e.fmt(f),
+            Self::Collision(e) => e.fmt(f),
+            Self::Io(e) => e.fmt(f),
+            Self::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Timeout(e) => Some(e),
+            Self::Checksum(e) => Some(e),
+            Self::Protocol(e) => Some(e),
+            Self::Exception(e) => Some(e),
+            Self::Security(e) => Some(e),
+            Self::Collision(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Other(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+/// 按类型转换，重试后仍失败（RetriesExhausted）时取最后一次的错误
+impl From<Error> for ClientError {
+    fn from(e: Error) -> Self {
+        let e = match e.downcast::<RetriesExhausted>() {
+            Ok(e) => e.source,
+            Err(e) => e,
+        };
+        let e = match e.downcast::<TimeoutError>() {
+            Ok(e) => return Self::Timeout(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<ChecksumError>() {
+            Ok(e) => return Self::Checksum(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<DecodeError>() {
+            Ok(e) => return Self::Protocol(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<Exception>() {
+            Ok(e) => return Self::Exception(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<SecurityError>() {
+            Ok(e) => return Self::Security(*e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<AddressCollision>() {
+            Ok(e) => return Self::Collision(*e),
+            Err(e) => e,
+        };
+        match e.downcast::<io::Error>() {
+            Ok(e) => Self::Io(*e),
+            Err(e) => Self::Other(e),
+        }
+    }
+}
+
+impl From<TimeoutError> for ClientError {
+    fn from(e: TimeoutError) -> Self {
+        Self::Timeout(e)
+    }
+}
+
+impl From<ChecksumError> for ClientError {
+    fn from(e: ChecksumError) -> Self {
+        Self::Checksum(e)
+    }
+}
+
+impl From<DecodeError> for ClientError {
+    fn from(e: DecodeError) -> Self {
+        Self::Protocol(e)
+    }
+}
+
+impl From<Exception> for ClientError {
+    fn from(e: Exception) -> Self {
+        Self::Exception(e)
+    }
+}
+
+impl From<SecurityError> for ClientError {
+    fn from(e: SecurityError) -> Self {
+        Self::Security(e)
+    }
+}
+
+impl From<AddressCollision> for ClientError {
+    fn from(e: AddressCollision) -> Self {
+        Self::Collision(e)
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn client_error() {
+        let e: Error = Box::new(RetriesExhausted {
+            attempts: 3,
+            source: Box::new(TimeoutError::Frame(Duration::from_secs(1))),
+        });
+        assert!(matches!(
+            ClientError::from(e),
+            ClientError::Timeout(TimeoutError::Frame(_))
+        ));
+        let e = ClientError::from(Box::new(Exception(0x04)) as Error);
+        assert!(matches!(e, ClientError::Exception(e) if e.unauthorized()));
+        let e = ClientError::from(Box::new(SecurityError(0x0004)) as Error);
+        assert!(matches!(e, ClientError::Security(e) if e.esam_verification_failed()));
+        assert_eq!(e.kind(), ErrorKind::Exception);
+        let e = ClientError::from(Box::new(io::Error::from(io::ErrorKind::NotConnected)) as Error);
+        assert_eq!(e.kind(), ErrorKind::Io);
+        let e = ClientError::from(Error::from("invalid data id"));
+        assert_eq!(e.to_string(), "invalid data id");
+        assert_eq!(e.kind(), ErrorKind::Other);
+    }
+}
@@ -2,7 +2,7 @@ use std::{io::Cursor, vec};
 
 use bytes::{Buf, Bytes};
 
-use crate::error::Error;
+use crate::error::{Error, Exception};
 
 #[derive(Clone, Debug)]
 pub enum Frame {}
@@ -22,6 +22,37 @@ impl Into<Error> for FrameError {
     }
 }
 
+/// 接收帧的校验码与帧内容不符
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChecksumError {
+    pub expected: u8,
+    pub actual: u8,
+}
+
+impl std::fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "checksum error; expect `{:02x}`, got `{:02x}`",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ChecksumError {}
+
+/// 接收的字节无法解码为帧，如起始符错误
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodeError(pub String);
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "protocol error; {}", self.0)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
 impl Frame {
     pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), FrameError> {
         // 跳过 0xfe
@@ -32,9 +63,10 @@ impl Frame {
                 if v == 0x68 {
                     break;
                 } else {
-                    return Err(FrameError::Other(
-                        format!("protocol error; invalid frame type byte `{}`", v).into(),
-                    ));
+                    return Err(FrameError::Other(Box::new(DecodeError(format!(
+                        "invalid frame type byte `{}`",
+                        v
+                    )))));
                 }
             }
         }
@@ -64,9 +96,10 @@ impl Frame {
                     b.push(0x68);
                     break;
                 } else {
-                    return Err(FrameError::Other(
-                        format!("protocol error; invalid frame type byte `{}`", v).into(),
-                    ));
+                    return Err(FrameError::Other(Box::new(DecodeError(format!(
+                        "invalid frame type byte `{}`",
+                        v
+                    )))));
                 }
             }
             b.push(0xfe);
@@ -85,10 +118,24 @@ impl Frame {
 
         b.extend_from_slice(get_u8_of(src, len as usize)?);
 
-        b.extend_from_slice(get_u8_of(src, 2)?);
+        // 校验码为第一个 68H 到校验码之前各字节的模 256 和
+        let start = b.iter().position(|v| *v == 0x68).unwrap_or(0);
+        let expected = ProtocolDataUnit::compute_cs(&b[start..].to_vec());
+        let tail = get_u8_of(src, 2)?;
+        if tail[0] != expected {
+            return Err(FrameError::Other(Box::new(ChecksumError {
+                expected,
+                actual: tail[0],
+            })));
+        }
+        b.extend_from_slice(tail);
         match ProtocolDataUnit::try_from(b) {
             Ok(f) => return Ok(f),
-            Err(e) => return Err(FrameError::Other(e.into())),
+            Err(_) => {
+                return Err(FrameError::Other(Box::new(DecodeError(
+                    "invalid frame".into(),
+                ))))
+            }
         }
     }
 }
@@ -103,9 +150,10 @@ fn get_u8(src: &mut Cursor<&[u8]>) -> Result<u8, FrameError> {
 fn get_u8_expect(src: &mut Cursor<&[u8]>, expect: u8) -> Result<(), FrameError> {
     let u8 = get_u8(src)?;
     if u8 != expect {
-        return Err(FrameError::Other(
-            format!("protocol error; invalid frame type byte `{}`", u8).into(),
-        ));
+        return Err(FrameError::Other(Box::new(DecodeError(format!(
+            "invalid frame type byte `{}`",
+            u8
+        )))));
     }
     Ok(())
 }
@@ -227,12 +275,12 @@ impl ProtocolDataUnit {
     pub fn read_addr() -> Result<Self, Error> {
         Self::from_cmd_2(vec![0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA], 0x13, &vec![])
     }
-    pub fn set_addr() -> Result<Self, Error> {
-        Self::from_cmd_2(
-            vec![0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA],
-            0x15,
-            &vec![vec![0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]],
-        )
+    /// 写通信地址，addr 为新地址（高字节在前），命令发往通配地址
+    pub fn set_addr(addr: Vec<u8>) -> Result<Self, Error> {
+        if addr.len() != 6 {
+            return Err(format!("invalid address `{}`", hex::encode(&addr)).into());
+        }
+        Self::from_cmd_2(vec![0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA], 0x15, &vec![addr])
     }
     pub fn address(&self) -> Vec<u8> {
         self.address.clone()
@@ -251,6 +299,68 @@ impl ProtocolDataUnit {
     pub fn c(&self) -> u8 {
         self.c
     }
+    /// 传送方向 D7：0 主站发出的命令帧，1 从站发出的应答帧
+    pub fn is_response(&self) -> bool {
+        self.c & 0x80 != 0
+    }
+    /// 从站应答标志 D6：1 异常应答
+    pub fn is_abnormal(&self) -> bool {
+        self.c & 0x40 != 0
+    }
+    /// 后续帧标志 D5：1 有后续数据帧
+    pub fn has_follow_up(&self) -> bool {
+        self.c & 0x20 != 0
+    }
+    /// 功能码 D4~D0
+    pub fn function(&self) -> u8 {
+        self.c & 0x1f
+    }
+    /// 数据域减 33H 后的内容，字节顺序与传输顺序一致（低字节在前）
+    pub fn payload(&self) -> Vec<u8> {
+        self.data.iter().map(|v| v.wrapping_sub(0x33)).collect()
+    }
+    /// 检查是否为 function 命令的正常应答，异常应答返回 Exception
+    pub fn check_reply(&self, function: u8) -> Result<(), Error> {
+        if !self.is_response() {
+            return Err(format!("not a response frame, c `{:02x}`", self.c).into());
+        }
+        if self.function() != function {
+            return Err(format!(
+                "unexpected function `{:02x}`, expect `{:02x}`",
+                self.function(),
+                function
+            )
+            .into());
+        }
+        if self.is_abnormal() {
+            return Err(Box::new(Exception::from_payload(&self.payload())));
+        }
+        Ok(())
+    }
+    /// 判断本帧是否为 request 的应答：传送方向、功能码、地址（通配字节及广播地址不比较）
+    /// 以及读数据、身份认证正常应答中回送的数据标识
+    pub fn is_reply_to(&self, request: &ProtocolDataUnit) -> bool {
+        if !self.is_response() || self.function() != request.function() {
+            return false;
+        }
+        let broadcast = request.address.iter().all(|b| *b == 0x99);
+        if !broadcast
+            && (self.address.len() != request.address.len()
+                || self
+                    .address
+                    .iter()
+                    .zip(&request.address)
+                    .any(|(a, r)| *r != 0xAA && a != r))
+        {
+            return false;
+        }
+        match request.function() {
+            0x03 | 0x11 | 0x12 if !self.is_abnormal() && request.data.len() >= 4 => {
+                self.data.get(..4) == Some(&request.data[..4])
+            }
+            _ => true,
+        }
+    }
 }
 impl Default for ProtocolDataUnit {
     fn default() -> Self {
@@ -363,6 +473,37 @@ mod tests {
     use super::*;
     use test::Bencher;
     #[test]
+    fn checksum() {
+        let mut adu: Vec<u8> = ProtocolDataUnit::read_addr().unwrap().into();
+        assert!(Frame::parse(&mut Cursor::new(&adu[..])).is_ok());
+        let len = adu.len();
+        adu[len - 2] ^= 0x01;
+        match Frame::parse(&mut Cursor::new(&adu[..])) {
+            Err(FrameError::Other(e)) => assert!(e.downcast_ref::<ChecksumError>().is_some()),
+            _ => panic!("checksum error expected"),
+        }
+    }
+    #[test]
+    fn is_reply_to() {
+        let di = vec![vec![0x00, 0x01, 0x00, 0x00]];
+        let request =
+            ProtocolDataUnit::from_cmd_2(hex::decode("202208310002").unwrap(), 0x11, &di).unwrap();
+        let reply = |addr: &str, c: u8, data: &Vec<Vec<u8>>| {
+            ProtocolDataUnit::from_cmd_2(hex::decode(addr).unwrap(), c, data).unwrap()
+        };
+        assert!(reply("202208310002", 0x91, &di).is_reply_to(&request));
+        assert!(reply("202208310002", 0xD1, &vec![vec![0x02]]).is_reply_to(&request));
+        // 回显的请求帧
+        assert!(!request.is_reply_to(&request));
+        assert!(!reply("202208310003", 0x91, &di).is_reply_to(&request));
+        assert!(!reply("202208310002", 0x94, &vec![]).is_reply_to(&request));
+        assert!(
+            !reply("202208310002", 0x91, &vec![vec![0x00, 0x02, 0x00, 0x00]]).is_reply_to(&request)
+        );
+        let wildcard = ProtocolDataUnit::read_addr().unwrap();
+        assert!(reply("202208310002", 0x93, &vec![]).is_reply_to(&wildcard));
+    }
+    #[test]
     fn from_cmd() {
         let pdu = ProtocolDataUnit::from_cmd("202208310002", "11", &vec!["028022FF"]);
         assert_eq!(pdu.is_ok(), true);
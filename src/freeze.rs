@@ -0,0 +1,123 @@
+use crate::address::MeterAddress;
+use crate::datetime::to_bcd;
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::transporter::Transporter;
+
+/// 冻结时间 MMDDhhmm，99 为通配
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FreezeTime {
+    /// 瞬时冻结 99999999
+    Now,
+    /// 每月 day 日 hour:minute 冻结
+    Monthly { day: u8, hour: u8, minute: u8 },
+    /// 每日 hour:minute 冻结
+    Daily { hour: u8, minute: u8 },
+    /// 每小时 minute 分冻结
+    Hourly { minute: u8 },
+}
+
+impl FreezeTime {
+    /// MMDDhhmm，高字节在前
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let (day, hour, minute) = match *self {
+            Self::Now => return Ok(vec![0x99; 4]),
+            Self::Monthly { day, hour, minute } => (Some(day), Some(hour), minute),
+            Self::Daily { hour, minute } => (None, Some(hour), minute),
+            Self::Hourly { minute } => (None, None, minute),
+        };
+        if day.is_some_and(|d| !(1..=28).contains(&d))
+            || hour.is_some_and(|h| h > 23)
+            || minute > 59
+        {
+            return Err(format!("invalid freeze time `{:?}`", self).into());
+        }
+        let bcd = |v: Option<u8>| v.map_or(0x99, to_bcd);
+        Ok(vec![0x99, bcd(day), bcd(hour), to_bcd(minute)])
+    }
+}
+
+/// 冻结命令（控制码 16H）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Freeze {
+    pub time: FreezeTime,
+}
+
+impl Freeze {
+    pub fn new(time: FreezeTime) -> Self {
+        Self { time }
+    }
+    pub fn to_pdu(&self, addr: &MeterAddress) -> Result<ProtocolDataUnit, Error> {
+        ProtocolDataUnit::from_cmd_2(addr.to_vec(), 0x16, &vec![self.time.to_bytes()?])
+    }
+    /// 正常应答 96H，异常应答 D6H
+    pub fn check_reply(reply: &ProtocolDataUnit) -> Result<(), Error> {
+        reply.check_reply(0x16)
+    }
+}
+
+/// 冻结电表数据。广播地址时所有电表同时冻结，不等待应答。
+pub async fn freeze<T: Transporter + Send + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    time: FreezeTime,
+) -> Result<(), Error> {
+    let adu: Vec<u8> = Freeze::new(time).to_pdu(addr)?.into();
+    if addr.is_broadcast() {
+        transporter.write(&adu).await?;
+        return Ok(());
+    }
+    match transporter.send(&adu).await? {
+        Some(reply) => Freeze::check_reply(&reply),
+        None => Err(format!("no response freezing `{}`", addr).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    #[test]
+    fn freeze_time() {
+        assert_eq!(FreezeTime::Now.to_bytes().unwrap(), vec![0x99; 4]);
+        assert_eq!(
+            FreezeTime::Monthly {
+                day: 1,
+                hour: 0,
+                minute: 0
+            }
+            .to_bytes()
+            .unwrap(),
+            vec![0x99, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(
+            FreezeTime::Hourly { minute: 30 }.to_bytes().unwrap(),
+            vec![0x99, 0x99, 0x99, 0x30]
+        );
+        assert!(FreezeTime::Daily {
+            hour: 24,
+            minute: 0
+        }
+        .to_bytes()
+        .is_err());
+    }
+    #[test]
+    fn freeze_now() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply("202208310002", 0x96, &[]);
+            let addr = "202208310002".parse().unwrap();
+            freeze(&mut t, &addr, FreezeTime::Now).await.unwrap();
+            // 广播冻结不等待应答
+            freeze(&mut t, &MeterAddress::broadcast(), FreezeTime::Now)
+                .await
+                .unwrap();
+            assert_eq!(t.sent.len(), 2);
+            assert!(t.replies.is_empty());
+            assert_eq!(hex::encode(t.sent[1].payload()), "99999999");
+        })
+    }
+}
@@ -1,16 +1,46 @@
 #![feature(test)]
 extern crate test;
 
+pub mod address;
+pub mod baud;
+pub mod blocking;
+pub mod broadcast;
+pub mod bus;
+pub mod catalog;
+pub mod client;
+pub mod clear;
+pub mod datetime;
+pub mod discovery;
 pub mod error;
 pub mod frame;
+pub mod freeze;
+pub mod output;
 pub mod packager;
+pub mod password;
+pub mod poll;
+pub mod probe;
+pub mod read;
+pub mod remote;
+pub mod retry;
 pub mod transporter;
 pub mod rs485;
+pub mod security;
+pub mod tariff;
 pub mod tcp;
+pub mod write;
+#[cfg(test)]
+mod mock;
 
+pub use address::MeterAddress;
+pub use blocking::BlockingClient;
+pub use catalog::{Catalog, DataId, Value};
+pub use client::Dlt645Client;
+pub use datetime::DateTime;
+pub use error::ClientError;
 pub use frame::Frame;
 pub use frame::ProtocolDataUnit;
 pub use packager::Packager;
+pub use password::{OperatorCode, Password};
 pub use transporter::Transporter;
 pub use rs485::RS485Transporter;
 pub use rs485::RS485Codec;
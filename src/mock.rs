@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::transporter::{TimeoutError, Transporter};
+
+/// 电表无应答
+pub fn timeout() -> Error {
+    Box::new(TimeoutError::Frame(Duration::from_secs(1)))
+}
+
+/// 按顺序回放预置应答的 Transporter，记录发送的每一帧
+#[derive(Default)]
+pub struct MockTransporter {
+    pub replies: VecDeque<Result<Option<ProtocolDataUnit>, Error>>,
+    pub sent: Vec<ProtocolDataUnit>,
+}
+
+impl MockTransporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 预置一帧应答，addr 按书写顺序，data 各段按高字节在前给出
+    pub fn reply(&mut self, addr: &str, c: u8, data: &[Vec<u8>]) -> &mut Self {
+        let pdu =
+            ProtocolDataUnit::from_cmd_2(hex::decode(addr).unwrap(), c, &data.to_vec()).unwrap();
+        self.replies.push_back(Ok(Some(pdu)));
+        self
+    }
+}
+
+#[async_trait]
+impl Transporter for MockTransporter {
+    async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+        self.sent
+            .push(ProtocolDataUnit::try_from(adu.to_vec()).map_err(Into::<Error>::into)?);
+        match self.replies.pop_front() {
+            Some(r) => r,
+            None => Err(timeout()),
+        }
+    }
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        self.sent
+            .push(ProtocolDataUnit::try_from(adu.to_vec()).map_err(Into::<Error>::into)?);
+        Ok(adu.len())
+    }
+    async fn open(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    async fn close(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
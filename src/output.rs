@@ -0,0 +1,106 @@
+use crate::address::MeterAddress;
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::transporter::Transporter;
+
+/// 多功能端子输出信号类别
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    /// 时钟秒脉冲 00H
+    SecondPulse,
+    /// 需量周期 01H
+    DemandPeriod,
+    /// 时段投切 02H
+    SegmentSwitch,
+}
+
+impl OutputMode {
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::SecondPulse => 0x00,
+            Self::DemandPeriod => 0x01,
+            Self::SegmentSwitch => 0x02,
+        }
+    }
+    pub fn from_code(code: u8) -> Result<Self, Error> {
+        match code {
+            0x00 => Ok(Self::SecondPulse),
+            0x01 => Ok(Self::DemandPeriod),
+            0x02 => Ok(Self::SegmentSwitch),
+            _ => Err(format!("invalid output mode `{:02x}`", code).into()),
+        }
+    }
+}
+
+/// 多功能端子输出控制命令（控制码 1DH）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetOutput {
+    pub mode: OutputMode,
+}
+
+impl SetOutput {
+    pub fn new(mode: OutputMode) -> Self {
+        Self { mode }
+    }
+    pub fn to_pdu(&self, addr: &MeterAddress) -> Result<ProtocolDataUnit, Error> {
+        ProtocolDataUnit::from_cmd_2(addr.to_vec(), 0x1D, &vec![vec![self.mode.code()]])
+    }
+    /// 正常应答 9DH 并返回当前输出信号类别，异常应答 DDH
+    pub fn check_reply(&self, reply: &ProtocolDataUnit) -> Result<(), Error> {
+        reply.check_reply(0x1D)?;
+        let mode = match reply.payload().as_slice() {
+            [code] => OutputMode::from_code(*code)?,
+            p => return Err(format!("invalid output mode reply `{}`", hex::encode(p)).into()),
+        };
+        if mode != self.mode {
+            return Err(format!(
+                "meter switched output to {:?}, expect {:?}",
+                mode, self.mode
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// 切换多功能端子输出信号
+pub async fn set_output<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    mode: OutputMode,
+) -> Result<(), Error> {
+    let request = SetOutput::new(mode);
+    let adu: Vec<u8> = request.to_pdu(addr)?.into();
+    match transporter.send(&adu).await? {
+        Some(reply) => request.check_reply(&reply),
+        None => Err(format!("no response setting output of `{}`", addr).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    #[test]
+    fn second_pulse() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x9D, &[vec![0x00]])
+                .reply(ADDR, 0x9D, &[vec![0x01]]);
+            let addr = ADDR.parse().unwrap();
+            set_output(&mut t, &addr, OutputMode::SecondPulse)
+                .await
+                .unwrap();
+            assert_eq!(t.sent[0].c(), 0x1D);
+            assert_eq!(hex::encode(t.sent[0].payload()), "00");
+            assert!(set_output(&mut t, &addr, OutputMode::SegmentSwitch)
+                .await
+                .is_err());
+        })
+    }
+}
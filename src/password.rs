@@ -0,0 +1,184 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::transporter::Transporter;
+
+/// 密码：权限 PA（0~9，0 为最高权限）及 3 字节密码 P2 P1 P0
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Password {
+    pub level: u8,
+    pub code: [u8; 3],
+}
+
+impl Password {
+    pub fn new(level: u8, code: [u8; 3]) -> Result<Self, Error> {
+        if level > 9 {
+            return Err(format!("invalid password level `{}`", level).into());
+        }
+        Ok(Self { level, code })
+    }
+    /// 数据域中的 4 字节，高字节在前（传输顺序为 PA P0 P1 P2）
+    pub(crate) fn segment(&self) -> Vec<u8> {
+        vec![self.code[0], self.code[1], self.code[2], self.level]
+    }
+}
+
+/// 按 PAP2P1P0 书写的 8 位十六进制数，如 `02123456`
+impl FromStr for Password {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = hex::decode(s)?;
+        if v.len() != 4 {
+            return Err(format!("invalid password `{}`", s).into());
+        }
+        Self::new(v[0], [v[1], v[2], v[3]])
+    }
+}
+
+// 不输出密码内容
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Password")
+            .field("level", &self.level)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 操作者代码 C3 C2 C1 C0
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OperatorCode(pub [u8; 4]);
+
+impl OperatorCode {
+    /// 数据域中的 4 字节，高字节在前（传输顺序为 C0 C1 C2 C3）
+    pub(crate) fn segment(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+}
+
+impl FromStr for OperatorCode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = hex::decode(s)?;
+        if v.len() != 4 {
+            return Err(format!("invalid operator code `{}`", s).into());
+        }
+        Ok(Self([v[0], v[1], v[2], v[3]]))
+    }
+}
+
+/// 修改密码命令（控制码 18H）
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangePassword {
+    pub di: DataId,
+    pub old: Password,
+    pub new: Password,
+}
+
+impl ChangePassword {
+    /// di 为被修改的密码权限对应的数据标识 04000C01~04000C0A（0~9 级），
+    /// 新密码权限必须与 di 一致，且不得高于用于认证的原密码权限（数值越小权限越高）
+    pub fn new(di: DataId, old: Password, new: Password) -> Result<Self, Error> {
+        match di.bytes() {
+            [0x04, 0x00, 0x0C, n @ 0x01..=0x0A] if n - 1 == new.level => {}
+            _ => {
+                return Err(format!(
+                    "data id `{}` does not match password level {}",
+                    di, new.level
+                )
+                .into())
+            }
+        }
+        if new.level < old.level {
+            return Err(format!(
+                "can not set level {} password with level {} password",
+                new.level, old.level
+            )
+            .into());
+        }
+        Ok(Self { di, old, new })
+    }
+    pub fn to_pdu(&self, addr: &MeterAddress) -> Result<ProtocolDataUnit, Error> {
+        ProtocolDataUnit::from_cmd_2(
+            addr.to_vec(),
+            0x18,
+            &vec![
+                self.di.bytes().to_vec(),
+                self.old.segment(),
+                self.new.segment(),
+            ],
+        )
+    }
+    /// 正常应答 98H 返回新密码，异常应答 D8H
+    pub fn check_reply(&self, reply: &ProtocolDataUnit) -> Result<(), Error> {
+        reply.check_reply(0x18)?;
+        let mut echo = self.new.segment();
+        echo.reverse();
+        let payload = reply.payload();
+        if !payload.is_empty() && payload != echo {
+            return Err("meter replied with a different password".into());
+        }
+        Ok(())
+    }
+}
+
+/// 修改密码
+pub async fn change_password<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    request: &ChangePassword,
+) -> Result<(), Error> {
+    let adu: Vec<u8> = request.to_pdu(addr)?.into();
+    match transporter.send(&adu).await? {
+        Some(reply) => request.check_reply(&reply),
+        None => Err(format!("no response changing password of `{}`", addr).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    #[test]
+    fn parse() {
+        let p: Password = "02123456".parse().unwrap();
+        assert_eq!(p.level, 2);
+        assert_eq!(p.code, [0x12, 0x34, 0x56]);
+        assert!(!format!("{:?}", p).contains("12"));
+        assert!("0a123456".parse::<Password>().is_err());
+        assert!("123456".parse::<OperatorCode>().is_err());
+    }
+    #[test]
+    fn change_password_rules() {
+        let p02: Password = "02000000".parse().unwrap();
+        let p04: Password = "04123456".parse().unwrap();
+        assert!(ChangePassword::new(DataId(0x04000C05), p02, p04).is_ok());
+        // 低权限密码不能设置高权限密码
+        assert!(ChangePassword::new(DataId(0x04000C03), p04, p02).is_err());
+        assert!(ChangePassword::new(DataId(0x04000C03), p02, p04).is_err());
+    }
+    #[test]
+    fn change_password() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply("202208310002", 0x98, &[vec![0x12, 0x34, 0x56, 0x04]]);
+            let addr = "202208310002".parse().unwrap();
+            let request = ChangePassword::new(
+                DataId(0x04000C05),
+                "02000000".parse().unwrap(),
+                "04123456".parse().unwrap(),
+            )
+            .unwrap();
+            super::change_password(&mut t, &addr, &request)
+                .await
+                .unwrap();
+            assert_eq!(hex::encode(t.sent[0].payload()), "050c00040200000004563412");
+        })
+    }
+}
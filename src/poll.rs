@@ -0,0 +1,356 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::stream::{self, select_all, Stream};
+
+use crate::address::MeterAddress;
+use crate::catalog::{DataId, Value};
+use crate::client::Dlt645Client;
+use crate::error::ClientError;
+use crate::transporter::Transporter;
+
+/// 最短抄读周期
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// 抄读任务：按固定周期读取一组数据标识
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PollJob {
+    pub dis: Vec<DataId>,
+    /// 小于 1ms 时按 1ms 计算
+    pub interval: Duration,
+    /// 对齐到整周期时刻（按 utc_offset 所在时区计算），如 15 分钟任务在 :00 :15 :30 :45 执行，
+    /// 1 天的任务在当地零点执行；不对齐时第一次立即执行
+    pub align: bool,
+    /// 对齐使用的时区，相对 UTC 的秒数，如东八区为 28800
+    pub utc_offset: i32,
+    /// 开始抄读某块表时已晚于计划时刻超过该值则跳过本次
+    pub max_delay: Option<Duration>,
+}
+
+impl PollJob {
+    pub fn new(dis: Vec<DataId>, interval: Duration) -> Self {
+        Self {
+            dis,
+            interval,
+            align: true,
+            utc_offset: 0,
+            max_delay: None,
+        }
+    }
+    pub fn with_align(mut self, align: bool) -> Self {
+        self.align = align;
+        self
+    }
+    pub fn with_utc_offset(mut self, seconds: i32) -> Self {
+        self.utc_offset = seconds;
+        self
+    }
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+    fn interval(&self) -> Duration {
+        self.interval.max(MIN_INTERVAL)
+    }
+    /// 第一次执行的计划时刻
+    pub fn first_due(&self, now: SystemTime) -> SystemTime {
+        if !self.align {
+            return now;
+        }
+        let since = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let offset = self.utc_offset as i128 * 1_000_000_000;
+        let interval = self.interval().as_nanos() as i128;
+        let local = since.as_nanos() as i128 + offset;
+        let due = (local.div_euclid(interval) + 1) * interval - offset;
+        UNIX_EPOCH + Duration::from_nanos(due.max(0) as u64)
+    }
+    /// scheduled 执行完后的下一个计划时刻，并返回因超时错过的周期数
+    pub fn advance(&self, scheduled: SystemTime, now: SystemTime) -> (SystemTime, u32) {
+        let interval = self.interval();
+        let next = scheduled + interval;
+        match now.duration_since(next) {
+            Ok(late) => {
+                let missed = late.as_nanos() / interval.as_nanos() + 1;
+                (
+                    next + interval * missed as u32,
+                    missed.min(u32::MAX as u128) as u32,
+                )
+            }
+            Err(_) => (next, 0),
+        }
+    }
+}
+
+/// 一个数据项的抄读结果
+#[derive(Debug)]
+pub struct Reading {
+    /// Poller::add_bus 返回的总线序号
+    pub bus: usize,
+    pub addr: MeterAddress,
+    pub di: DataId,
+    /// 计划时刻
+    pub scheduled: SystemTime,
+    /// 收到应答的时刻
+    pub timestamp: SystemTime,
+    /// 本次之前该任务对该表错过的周期数
+    pub missed: u32,
+    pub value: Result<Value, ClientError>,
+}
+
+struct Scheduled {
+    job: PollJob,
+    due: SystemTime,
+    /// 各表错过的周期数
+    missed: Vec<u32>,
+}
+
+/// 正在执行的任务及下一个要读的表和数据标识
+#[derive(Clone, Copy)]
+struct Cursor {
+    job: usize,
+    meter: usize,
+    di: usize,
+}
+
+struct BusPoller<T: Transporter> {
+    index: usize,
+    client: Dlt645Client<T>,
+    meters: Vec<MeterAddress>,
+    jobs: Vec<Scheduled>,
+    cursor: Option<Cursor>,
+}
+
+impl<T: Transporter + Send> BusPoller<T> {
+    /// 每读完一个数据项即返回其结果
+    async fn next(&mut self) -> Option<Reading> {
+        loop {
+            let cursor = match self.cursor {
+                Some(cursor) => cursor,
+                None => {
+                    let job = (0..self.jobs.len()).min_by_key(|&i| self.jobs[i].due)?;
+                    if let Ok(wait) = self.jobs[job].due.duration_since(SystemTime::now()) {
+                        tokio::time::sleep(wait).await;
+                    }
+                    Cursor {
+                        job,
+                        meter: 0,
+                        di: 0,
+                    }
+                }
+            };
+            if let Some(r) = self.step(cursor).await {
+                return Some(r);
+            }
+        }
+    }
+    /// 读 cursor 处的数据项并前移 cursor；跳过的表、读完一块表及任务执行完时返回 None
+    async fn step(&mut self, cursor: Cursor) -> Option<Reading> {
+        let Scheduled { job, due, missed } = &mut self.jobs[cursor.job];
+        let scheduled = *due;
+        let m = cursor.meter;
+        let addr = match self.meters.get(m) {
+            Some(addr) => *addr,
+            None => {
+                let (next, skipped) = job.advance(scheduled, SystemTime::now());
+                *due = next;
+                for n in missed.iter_mut() {
+                    *n = n.saturating_add(skipped);
+                }
+                self.cursor = None;
+                return None;
+            }
+        };
+        let next_meter = Cursor {
+            meter: m + 1,
+            di: 0,
+            ..cursor
+        };
+        if cursor.di == 0 {
+            let late = SystemTime::now()
+                .duration_since(scheduled)
+                .unwrap_or_default();
+            if job.max_delay.is_some_and(|d| late > d) {
+                missed[m] += 1;
+                self.cursor = Some(next_meter);
+                return None;
+            }
+        }
+        let di = match job.dis.get(cursor.di) {
+            Some(di) => *di,
+            None => {
+                missed[m] = 0;
+                self.cursor = Some(next_meter);
+                return None;
+            }
+        };
+        let value = self.client.read(&addr, di).await;
+        self.cursor = Some(Cursor {
+            di: cursor.di + 1,
+            ..cursor
+        });
+        Some(Reading {
+            bus: self.index,
+            addr,
+            di,
+            scheduled,
+            timestamp: SystemTime::now(),
+            missed: missed[m],
+            value,
+        })
+    }
+}
+
+/// 多表轮询：各总线之间并行，同一总线上的请求依次执行。
+/// 同一总线上计划时刻相同的任务按添加顺序执行，总线繁忙导致错过的周期不补抄，
+/// 记入下一次结果的 missed。
+pub struct Poller<T: Transporter> {
+    jobs: Vec<PollJob>,
+    buses: Vec<BusPoller<T>>,
+}
+
+impl<T: Transporter + Send> Poller<T> {
+    /// 各总线上的每块表都执行 jobs 中的全部任务
+    pub fn new(jobs: Vec<PollJob>) -> Self {
+        Self {
+            jobs,
+            buses: Vec::new(),
+        }
+    }
+    /// 添加一条总线及其上的电表，返回总线序号
+    pub fn add_bus(&mut self, client: Dlt645Client<T>, meters: Vec<MeterAddress>) -> usize {
+        let now = SystemTime::now();
+        let index = self.buses.len();
+        let jobs = self
+            .jobs
+            .iter()
+            .map(|job| Scheduled {
+                job: job.clone(),
+                due: job.first_due(now),
+                missed: vec![0; meters.len()],
+            })
+            .collect();
+        self.buses.push(BusPoller {
+            index,
+            client,
+            meters,
+            jobs,
+            cursor: None,
+        });
+        index
+    }
+    /// 开始轮询，按完成顺序输出各总线的抄读结果
+    pub fn into_stream(self) -> impl Stream<Item = Reading>
+    where
+        T: 'static,
+    {
+        select_all(self.buses.into_iter().map(|bus| {
+            Box::pin(stream::unfold(bus, |mut bus| async move {
+                let r = bus.next().await?;
+                Some((r, bus))
+            }))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::join;
+    use futures::StreamExt;
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::bus::BusHandle;
+    use crate::mock::MockTransporter;
+
+    #[test]
+    fn schedule() {
+        let job = PollJob::new(vec![], Duration::from_secs(900));
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        assert_eq!(job.first_due(now), UNIX_EPOCH + Duration::from_secs(1800));
+        let due = UNIX_EPOCH + Duration::from_secs(1800);
+        assert_eq!(
+            job.advance(due, due + Duration::from_secs(10)),
+            (due + Duration::from_secs(900), 0)
+        );
+        // 执行超过 3 个周期，错过 3 次
+        assert_eq!(
+            job.advance(due, due + Duration::from_secs(3000)),
+            (due + Duration::from_secs(3600), 3)
+        );
+        let job = job.with_align(false);
+        assert_eq!(job.first_due(now), now);
+        // 按东八区零点对齐
+        let day = PollJob::new(vec![], Duration::from_secs(86400)).with_utc_offset(28800);
+        assert_eq!(day.first_due(now), UNIX_EPOCH + Duration::from_secs(57600));
+        let day = day.with_utc_offset(-3600);
+        assert_eq!(day.first_due(now), UNIX_EPOCH + Duration::from_secs(3600));
+        // 周期为零时按最短周期计算
+        let mut job = PollJob::new(vec![], Duration::from_secs(1));
+        job.interval = Duration::ZERO;
+        assert!(job.first_due(now) > now);
+        assert_eq!(job.advance(due, due).0, due + MIN_INTERVAL);
+    }
+    #[test]
+    fn poll() {
+        block_on(async {
+            let a = "202208310001";
+            let b = "202208310002";
+            let di = vec![0x00, 0x01, 0x00, 0x00];
+            let mut t = MockTransporter::new();
+            t.reply(a, 0x91, &[di.clone(), vec![0x00, 0x00, 0x12, 0x34]])
+                .reply(a, 0x91, &[di.clone(), vec![0x00, 0x00, 0x12, 0x35]]);
+            let mut poller = Poller::new(vec![PollJob::new(
+                vec![DataId(0x00010000)],
+                Duration::from_millis(50),
+            )
+            .with_align(false)]);
+            poller.add_bus(Dlt645Client::new(t), vec![a.parse().unwrap()]);
+            let mut t = MockTransporter::new();
+            t.reply(b, 0x91, &[di.clone(), vec![0x00, 0x00, 0x00, 0x01]]);
+            poller.add_bus(Dlt645Client::new(t), vec![b.parse().unwrap()]);
+            let readings: Vec<Reading> = poller.into_stream().take(4).collect().await;
+            let bus0: Vec<&Reading> = readings.iter().filter(|r| r.bus == 0).collect();
+            let bus1: Vec<&Reading> = readings.iter().filter(|r| r.bus == 1).collect();
+            assert_eq!(bus0.len(), 2);
+            assert_eq!(bus1.len(), 2);
+            assert_eq!(bus0[0].value.as_ref().unwrap().to_string(), "12.34");
+            assert_eq!(bus0[1].value.as_ref().unwrap().to_string(), "12.35");
+            assert_eq!(
+                bus0[1].scheduled.duration_since(bus0[0].scheduled).unwrap(),
+                Duration::from_millis(50)
+            );
+            assert_eq!(bus1[0].addr.to_string(), b);
+            assert!(bus1[1].value.is_err());
+        })
+    }
+    #[test]
+    fn each_reading() {
+        block_on(async {
+            let a = "202208310001";
+            let mut t = MockTransporter::new();
+            t.reply(
+                a,
+                0x91,
+                &[vec![0x00, 0x01, 0x00, 0x00], vec![0x00, 0x00, 0x12, 0x34]],
+            )
+            .reply(
+                a,
+                0x91,
+                &[vec![0x00, 0x02, 0x00, 0x00], vec![0x00, 0x00, 0x12, 0x35]],
+            );
+            let (bus, actor) = BusHandle::new(t);
+            let mut poller = Poller::new(vec![PollJob::new(
+                vec![DataId(0x00010000), DataId(0x00020000)],
+                Duration::from_secs(60),
+            )
+            .with_align(false)]);
+            poller.add_bus(Dlt645Client::new(bus), vec![a.parse().unwrap()]);
+            let (t, readings) = join(actor.run(), async move {
+                poller.into_stream().take(1).collect::<Vec<Reading>>().await
+            })
+            .await;
+            // 读完第一项即输出，不等待同一任务的其他数据项
+            assert_eq!(readings[0].di, DataId(0x00010000));
+            assert_eq!(t.sent.len(), 1);
+        })
+    }
+}
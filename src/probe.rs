@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::address::MeterAddress;
+use crate::catalog::{Catalog, DataId};
+use crate::discovery::ADDRESS_DI;
+use crate::error::{Error, Exception};
+use crate::frame::ProtocolDataUnit;
+use crate::read::read;
+use crate::retry::ErrorKind;
+use crate::transporter::Transporter;
+
+/// DL/T645-1997 正向有功总电能，用于识别 1997 版电表
+const ENERGY_1997: [u8; 2] = [0x90, 0x10];
+
+/// 协议版本
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    /// DL/T645-1997，读数据控制码 01H，数据标识 2 字节
+    V1997,
+    /// DL/T645-2007，读数据控制码 11H，数据标识 4 字节
+    V2007,
+}
+
+/// 数据标识的支持情况
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Support {
+    Supported,
+    /// 电表应答无请求数据
+    Unsupported,
+    /// 超时、应答无法解码等，不能确定是否支持
+    Error(String),
+}
+
+/// 探测项
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProbeSet {
+    /// 逐项读取的数据标识，须在目录中且可读
+    pub dis: Vec<DataId>,
+    /// 用于判断是否支持数据块读取的数据块标识
+    pub blocks: Vec<DataId>,
+}
+
+impl Default for ProbeSet {
+    /// 常用的电能、瞬时量和参数
+    fn default() -> Self {
+        Self {
+            dis: [
+                0x00000000, 0x00010000, 0x00020000, 0x00010100, 0x00010200, 0x00010300, 0x00010400,
+                0x02010100, 0x02010200, 0x02010300, 0x02020100, 0x02020200, 0x02020300, 0x02030000,
+                0x04000101, 0x04000102,
+            ]
+            .into_iter()
+            .map(DataId)
+            .collect(),
+            blocks: vec![DataId(0x0001FF00), DataId(0x0201FF00)],
+        }
+    }
+}
+
+/// 电表能力，可序列化保存，供抄读计划选择请求方式
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeterProfile {
+    pub address: MeterAddress,
+    pub protocol: Protocol,
+    /// 各数据标识（含数据块）的支持情况
+    pub dis: BTreeMap<DataId, Support>,
+    /// 支持数据块读取
+    pub block_read: bool,
+    /// 本次探测中收到的最长应答帧字节数（不含前导字节）。
+    /// 只反映探测项的应答长度，不是电表支持的最大帧长
+    pub longest_reply_observed: usize,
+    /// 不发送 FEH 前导字节时电表不应答；无法判断时为 None
+    pub preamble_required: Option<bool>,
+}
+
+impl MeterProfile {
+    pub fn supports(&self, di: DataId) -> bool {
+        self.dis.get(&di) == Some(&Support::Supported)
+    }
+    /// 支持的数据标识
+    pub fn supported(&self) -> impl Iterator<Item = DataId> + '_ {
+        self.dis
+            .iter()
+            .filter(|(_, s)| **s == Support::Supported)
+            .map(|(di, _)| *di)
+    }
+}
+
+/// 记录最长应答帧的 Transporter
+struct Observe<'a, T: ?Sized> {
+    inner: &'a mut T,
+    longest_reply_observed: usize,
+}
+
+#[async_trait]
+impl<T: Transporter + Send + ?Sized> Transporter for Observe<'_, T> {
+    async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+        let reply = self.inner.send(adu).await?;
+        if let Some(reply) = &reply {
+            // 帧头 10 字节 + 数据域 + 校验码及结束符
+            self.longest_reply_observed = self.longest_reply_observed.max(12 + reply.data().len());
+        }
+        Ok(reply)
+    }
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        self.inner.write(adu).await
+    }
+    async fn open(&mut self) -> Result<(), Error> {
+        self.inner.open().await
+    }
+    async fn close(&mut self) -> Result<(), Error> {
+        self.inner.close().await
+    }
+    fn begin_transaction(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        self.inner.begin_transaction()
+    }
+    fn end_transaction(&mut self) {
+        self.inner.end_transaction()
+    }
+}
+
+/// 发送 adu，有应答且控制码符合时返回 true，无应答返回 false
+async fn answers<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    adu: &[u8],
+    function: u8,
+) -> Result<bool, Error> {
+    match transporter.send(adu).await {
+        Ok(Some(reply)) => Ok(reply.is_response() && reply.function() == function),
+        Ok(None) => Ok(false),
+        Err(e) if ErrorKind::of(&e) == ErrorKind::Timeout => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn classify(r: Result<(), Error>) -> Support {
+    match r {
+        Ok(()) => Support::Supported,
+        Err(e) => match e.downcast_ref::<Exception>() {
+            Some(e) if e.no_data() => Support::Unsupported,
+            _ => Support::Error(e.to_string()),
+        },
+    }
+}
+
+/// 探测电表能力：识别协议版本和是否需要前导字节，再按目录逐项读取 set 中的数据标识和数据块，
+/// 能按目录解码的记为支持。1997 版电表只识别版本，不读取数据标识。
+pub async fn probe<T: Transporter + Send + ?Sized>(
+    transporter: &mut T,
+    catalog: &Catalog,
+    addr: &MeterAddress,
+    set: &ProbeSet,
+) -> Result<MeterProfile, Error> {
+    for di in &set.dis {
+        match catalog.lookup(*di) {
+            Some(item) if item.access.readable() => {}
+            _ => return Err(format!("can not probe `{}`: not a readable catalog item", di).into()),
+        }
+    }
+    for di in &set.blocks {
+        catalog.block_members(*di)?;
+    }
+    let mut t = Observe {
+        inner: transporter,
+        longest_reply_observed: 0,
+    };
+    let v2007: Vec<u8> =
+        ProtocolDataUnit::from_cmd_2(addr.to_vec(), 0x11, &vec![ADDRESS_DI.bytes().to_vec()])?
+            .into();
+    let v1997: Vec<u8> =
+        ProtocolDataUnit::from_cmd_2(addr.to_vec(), 0x01, &vec![ENERGY_1997.to_vec()])?.into();
+    // 异常应答同样说明电表支持该版本
+    let (protocol, adu, function) = if answers(&mut t, &v2007, 0x11).await? {
+        (Protocol::V2007, v2007, 0x11)
+    } else if answers(&mut t, &v1997, 0x01).await? {
+        (Protocol::V1997, v1997, 0x01)
+    } else {
+        return Err(format!("no response probing `{}`", addr).into());
+    };
+    // 去掉前导字节重发
+    let start = adu.iter().position(|b| *b != 0xfe).unwrap_or(0);
+    let preamble_required = match answers(&mut t, &adu[start..], function).await {
+        Ok(answered) => Some(!answered),
+        Err(_) => None,
+    };
+    let mut dis = BTreeMap::new();
+    if protocol == Protocol::V2007 {
+        for di in &set.dis {
+            let r = match read(&mut t, addr, *di).await {
+                Ok(data) => catalog.decode(*di, &data).map(|_| ()),
+                Err(e) => Err(e),
+            };
+            dis.insert(*di, classify(r));
+        }
+        for di in &set.blocks {
+            let r = match read(&mut t, addr, *di).await {
+                Ok(data) => catalog.expand_block(*di, &data).map(|_| ()),
+                Err(e) => Err(e),
+            };
+            dis.insert(*di, classify(r));
+        }
+    }
+    let block_read = set
+        .blocks
+        .iter()
+        .any(|di| dis.get(di) == Some(&Support::Supported));
+    Ok(MeterProfile {
+        address: *addr,
+        protocol,
+        dis,
+        block_read,
+        longest_reply_observed: t.longest_reply_observed,
+        preamble_required,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::{timeout, MockTransporter};
+
+    const ADDR: &str = "202208310002";
+
+    #[test]
+    fn default_set() {
+        let catalog = Catalog::new();
+        let set = ProbeSet::default();
+        assert!(set.dis.iter().all(|di| catalog.lookup(*di).is_some()));
+        assert!(set
+            .blocks
+            .iter()
+            .all(|di| catalog.block_members(*di).is_ok()));
+    }
+    #[test]
+    fn probe() {
+        block_on(async {
+            let addr: MeterAddress = ADDR.parse().unwrap();
+            let address = vec![ADDRESS_DI.bytes().to_vec(), addr.to_vec()];
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x91, &address);
+            // 不带前导字节时不应答
+            t.replies.push_back(Err(timeout()));
+            t.reply(
+                ADDR,
+                0x91,
+                &[vec![0x00, 0x01, 0x00, 0x00], vec![0x00, 0x00, 0x12, 0x34]],
+            )
+            .reply(ADDR, 0xD1, &[vec![0x02]])
+            .reply(
+                ADDR,
+                0x91,
+                &[
+                    vec![0x00, 0x01, 0xFF, 0x00],
+                    vec![0x00, 0x00, 0x12, 0x34],
+                    vec![0x00, 0x00, 0x10, 0x00],
+                ],
+            );
+            let set = ProbeSet {
+                dis: vec![DataId(0x00010000), DataId(0x02010100)],
+                blocks: vec![DataId(0x0001FF00)],
+            };
+            let profile = super::probe(&mut t, &Catalog::new(), &addr, &set)
+                .await
+                .unwrap();
+            assert_eq!(t.sent.len(), 5);
+            assert_eq!(profile.protocol, Protocol::V2007);
+            assert_eq!(profile.preamble_required, Some(true));
+            assert!(profile.supports(DataId(0x00010000)));
+            assert_eq!(profile.dis[&DataId(0x02010100)], Support::Unsupported);
+            assert!(profile.block_read);
+            assert_eq!(profile.longest_reply_observed, 24);
+            let json = serde_json::to_string(&profile).unwrap();
+            assert!(json.contains(r#""0001ff00":"supported""#));
+            let back: MeterProfile = serde_json::from_str(&json).unwrap();
+            assert_eq!(back, profile);
+
+            // 1997 版电表
+            let mut t = MockTransporter::new();
+            t.replies.push_back(Err(timeout()));
+            t.reply(
+                ADDR,
+                0x81,
+                &[ENERGY_1997.to_vec(), vec![0x00, 0x00, 0x12, 0x34]],
+            )
+            .reply(
+                ADDR,
+                0x81,
+                &[ENERGY_1997.to_vec(), vec![0x00, 0x00, 0x12, 0x34]],
+            );
+            let profile = super::probe(&mut t, &Catalog::new(), &addr, &set)
+                .await
+                .unwrap();
+            assert_eq!(profile.protocol, Protocol::V1997);
+            assert_eq!(profile.preamble_required, Some(false));
+            assert!(profile.dis.is_empty());
+            assert!(!profile.block_read);
+
+            let mut t = MockTransporter::new();
+            assert!(super::probe(&mut t, &Catalog::new(), &addr, &set)
+                .await
+                .is_err());
+        })
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+
+use crate::address::MeterAddress;
+use crate::catalog::{Catalog, DataId, Value};
+use crate::error::{Error, Exception};
+use crate::frame::ProtocolDataUnit;
+use crate::transporter::Transporter;
+
+/// 读数据时默认最多接收的帧数（含后续帧）
+pub const DEFAULT_MAX_FRAMES: usize = 16;
+
+/// 读数据，返回数据标识之后的数据（已减 33H，低字节在前）。
+/// 有后续数据帧时自动读取后续数据并拼接，最多接收 DEFAULT_MAX_FRAMES 帧，期间独占共享总线。
+pub async fn read<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    di: DataId,
+) -> Result<Vec<u8>, Error> {
+    read_with_max_frames(transporter, addr, di, DEFAULT_MAX_FRAMES).await
+}
+
+/// 同 read，最多接收 max_frames 帧，超出时返回错误
+pub async fn read_with_max_frames<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    di: DataId,
+    max_frames: usize,
+) -> Result<Vec<u8>, Error> {
+    transporter.begin_transaction().await?;
+    let r = read_frames(transporter, addr, di, max_frames).await;
+    transporter.end_transaction();
+    r
+}
+
+/// 读数据并接收后续帧，调用方已开始多帧交互
+async fn read_frames<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    di: DataId,
+    max_frames: usize,
+) -> Result<Vec<u8>, Error> {
+    let segments = vec![di.bytes().to_vec()];
+    let reply = request(transporter, addr, 0x11, &segments, di).await?;
+    let payload = reply.payload();
+    let mut data = payload[4..].to_vec();
+    let mut follow_up = reply.has_follow_up();
+    let mut frames = 1;
+    // 读后续数据，帧序号从 1 开始递增
+    let mut seq: u8 = 0;
+    while follow_up {
+        if frames >= max_frames {
+            return Err(format!("reading `{}` exceeds {} frames", di, max_frames).into());
+        }
+        seq = seq.wrapping_add(1);
+        let segments = vec![di.bytes().to_vec(), vec![seq]];
+        let reply = request(transporter, addr, 0x12, &segments, di).await?;
+        let payload = reply.payload();
+        // 数据标识 + 数据 + 帧序号
+        match payload.last() {
+            Some(echo) if payload.len() > 4 && *echo == seq => {}
+            _ => {
+                return Err(format!(
+                    "unexpected follow-up frame `{}` reading `{}`, expect seq {}",
+                    hex::encode(&payload),
+                    di,
+                    seq
+                )
+                .into())
+            }
+        }
+        data.extend_from_slice(&payload[4..payload.len() - 1]);
+        follow_up = reply.has_follow_up();
+        frames += 1;
+    }
+    Ok(data)
+}
+
+/// 发送读命令并检查应答的控制码和数据标识
+async fn request<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    c: u8,
+    segments: &Vec<Vec<u8>>,
+    di: DataId,
+) -> Result<ProtocolDataUnit, Error> {
+    let adu: Vec<u8> = ProtocolDataUnit::from_cmd_2(addr.to_vec(), c, segments)?.into();
+    let reply = match transporter.send(&adu).await? {
+        Some(reply) => reply,
+        None => return Err(format!("no response reading `{}`", di).into()),
+    };
+    reply.check_reply(c)?;
+    let echo = DataId::from_wire(&reply.payload())?;
+    if echo != di {
+        return Err(format!("unexpected data id `{}`, expect `{}`", echo, di).into());
+    }
+    Ok(reply)
+}
+
+/// 读单个数据项并按目录解码
+pub async fn read_value<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    catalog: &Catalog,
+    addr: &MeterAddress,
+    di: DataId,
+) -> Result<Value, Error> {
+    let data = read(transporter, addr, di).await?;
+    catalog.decode(di, &data)
+}
+
+/// 读数据块并拆分为各数据项。
+/// 电表对数据块应答“无请求数据”时，改为逐项读取，直到某一项无数据为止。
+pub async fn read_block<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    catalog: &Catalog,
+    addr: &MeterAddress,
+    di: DataId,
+) -> Result<BTreeMap<DataId, Value>, Error> {
+    read_block_with_max_frames(transporter, catalog, addr, di, DEFAULT_MAX_FRAMES).await
+}
+
+/// 同 read_block，每次读取最多接收 max_frames 帧
+pub async fn read_block_with_max_frames<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    catalog: &Catalog,
+    addr: &MeterAddress,
+    di: DataId,
+    max_frames: usize,
+) -> Result<BTreeMap<DataId, Value>, Error> {
+    let members = catalog.block_members(di)?;
+    match read_with_max_frames(transporter, addr, di, max_frames).await {
+        Ok(data) => return catalog.expand_block(di, &data),
+        Err(e) if is_no_data(&e) => {}
+        Err(e) => return Err(e),
+    }
+    let mut values = BTreeMap::new();
+    for item in members {
+        match read_with_max_frames(transporter, addr, item.id, max_frames).await {
+            Ok(data) => {
+                values.insert(item.id, item.decode(&data)?);
+            }
+            Err(e) if is_no_data(&e) && !values.is_empty() => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(values)
+}
+
+fn is_no_data(e: &Error) -> bool {
+    e.downcast_ref::<Exception>().is_some_and(|e| e.no_data())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    #[test]
+    fn read_value() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(
+                ADDR,
+                0x91,
+                &[vec![0x00, 0x01, 0x00, 0x00], vec![0x00, 0x01, 0x23, 0x45]],
+            );
+            let addr = ADDR.parse().unwrap();
+            let v = super::read_value(&mut t, &Catalog::new(), &addr, DataId(0x00010000))
+                .await
+                .unwrap();
+            assert_eq!(v.to_string(), "123.45");
+            assert_eq!(
+                Into::<String>::into(t.sent[0].clone()),
+                "fefefefe68020031082220681104333334332f16".to_string()
+            );
+        })
+    }
+    #[test]
+    fn read_abnormal() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0xD1, &[vec![0x02]]);
+            let addr = ADDR.parse().unwrap();
+            let e = read(&mut t, &addr, DataId(0x00010000)).await.unwrap_err();
+            assert_eq!(e.downcast_ref::<Exception>(), Some(&Exception(0x02)));
+        })
+    }
+    #[test]
+    fn read_block_fallback() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0xD1, &[vec![0x02]])
+                .reply(
+                    ADDR,
+                    0x91,
+                    &[vec![0x02, 0x01, 0x01, 0x00], vec![0x22, 0x00]],
+                )
+                .reply(
+                    ADDR,
+                    0x91,
+                    &[vec![0x02, 0x01, 0x02, 0x00], vec![0x22, 0x01]],
+                )
+                .reply(ADDR, 0xD1, &[vec![0x02]]);
+            let addr = ADDR.parse().unwrap();
+            let values = read_block(&mut t, &Catalog::new(), &addr, DataId(0x0201FF00))
+                .await
+                .unwrap();
+            assert_eq!(t.sent.len(), 4);
+            assert_eq!(values.len(), 2);
+            assert_eq!(values[&DataId(0x02010200)].to_string(), "220.1");
+        })
+    }
+    #[test]
+    fn read_follow_up() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            let di = vec![0x04, 0x01, 0x00, 0x00];
+            t.reply(ADDR, 0xB1, &[di.clone(), vec![0x01, 0x01, 0x01]])
+                .reply(
+                    ADDR,
+                    0xB2,
+                    &[di.clone(), vec![0x06, 0x01, 0x02], vec![0x01]],
+                )
+                .reply(
+                    ADDR,
+                    0x92,
+                    &[di.clone(), vec![0x10, 0x01, 0x01], vec![0x02]],
+                );
+            let addr = ADDR.parse().unwrap();
+            let data = read(&mut t, &addr, DataId(0x04010000)).await.unwrap();
+            assert_eq!(hex::encode(data), "010101020106010110");
+            assert_eq!(t.sent[1].c(), 0x12);
+            assert_eq!(hex::encode(t.sent[2].payload()), "0000010402");
+        })
+    }
+    #[test]
+    fn read_follow_up_limit() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            let di = vec![0x04, 0x01, 0x00, 0x00];
+            t.reply(ADDR, 0xB1, &[di.clone(), vec![0x01]]).reply(
+                ADDR,
+                0xB2,
+                &[di.clone(), vec![0x02], vec![0x01]],
+            );
+            let addr: MeterAddress = ADDR.parse().unwrap();
+            let e = read_with_max_frames(&mut t, &addr, DataId(0x04010000), 2).await;
+            assert!(e.is_err());
+            // 帧序号不符
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0xB1, &[di.clone(), vec![0x01]]).reply(
+                ADDR,
+                0x92,
+                &[di.clone(), vec![0x02], vec![0x02]],
+            );
+            assert!(read(&mut t, &addr, DataId(0x04010000)).await.is_err());
+        })
+    }
+}
@@ -0,0 +1,306 @@
+use std::fmt;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::datetime::{to_bcd, DateTime};
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::password::{OperatorCode, Password};
+use crate::read::read;
+use crate::transporter::Transporter;
+
+/// 电表运行状态字 3
+pub const STATUS_WORD_3: DataId = DataId(0x04000503);
+/// 默认等待电表执行命令的时间，电表可配置跳闸、合闸延时
+pub const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+/// 默认读运行状态字 3 的间隔
+pub const DEFAULT_CONFIRM_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 控制命令类型 N1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlType {
+    /// 跳闸 1AH
+    Trip,
+    /// 合闸允许 1BH
+    AllowClose,
+    /// 直接合闸 1CH
+    DirectClose,
+    /// 报警 2AH
+    Alarm,
+    /// 报警解除 2BH
+    AlarmRelease,
+    /// 保电 3AH
+    KeepPower,
+    /// 保电解除 3BH
+    KeepPowerRelease,
+}
+
+impl ControlType {
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Trip => 0x1A,
+            Self::AllowClose => 0x1B,
+            Self::DirectClose => 0x1C,
+            Self::Alarm => 0x2A,
+            Self::AlarmRelease => 0x2B,
+            Self::KeepPower => 0x3A,
+            Self::KeepPowerRelease => 0x3B,
+        }
+    }
+    /// 命令执行后运行状态字 3 中应达到的状态
+    fn is_done(&self, status: RelayStatus) -> bool {
+        match self {
+            Self::Trip => status.relay_open(),
+            // 合闸允许后由用户手动合闸，只确认命令状态
+            Self::AllowClose => !status.relay_command_open(),
+            Self::DirectClose => !status.relay_open(),
+            Self::Alarm => status.pre_trip_alarm(),
+            Self::AlarmRelease => !status.pre_trip_alarm(),
+            Self::KeepPower => status.keep_power(),
+            Self::KeepPowerRelease => !status.keep_power(),
+        }
+    }
+}
+
+/// 运行状态字 3 中与远程控制相关的状态位
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelayStatus(pub u16);
+
+impl RelayStatus {
+    /// bit4 继电器状态，1 为断
+    pub fn relay_open(&self) -> bool {
+        self.0 & 0x0010 != 0
+    }
+    /// bit6 继电器命令状态，1 为断
+    pub fn relay_command_open(&self) -> bool {
+        self.0 & 0x0040 != 0
+    }
+    /// bit7 预跳闸报警状态
+    pub fn pre_trip_alarm(&self) -> bool {
+        self.0 & 0x0080 != 0
+    }
+    /// bit12 保电状态
+    pub fn keep_power(&self) -> bool {
+        self.0 & 0x1000 != 0
+    }
+}
+
+/// 控制命令数据块 N1~N8 的加密接口，不同厂家或安全模块按各自规则实现
+pub trait CommandCipher {
+    /// block 为传输顺序的 N1~N8，返回传输顺序的密文
+    fn encrypt(&self, block: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+/// 不加密，按明文发送
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlainText;
+
+impl CommandCipher for PlainText {
+    fn encrypt(&self, block: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(block.to_vec())
+    }
+}
+
+/// 远程控制命令（控制码 1CH）
+#[derive(Clone, Debug)]
+pub struct RemoteControl {
+    pub control: ControlType,
+    /// 命令有效截止时间
+    pub deadline: DateTime,
+    pub password: Password,
+    pub operator: OperatorCode,
+    /// 电表应答后等待运行状态字 3 达到目标状态的时间
+    pub confirm_timeout: Duration,
+    /// 确认期间读运行状态字 3 的间隔
+    pub confirm_interval: Duration,
+}
+
+impl RemoteControl {
+    pub fn new(
+        control: ControlType,
+        deadline: DateTime,
+        password: Password,
+        operator: OperatorCode,
+    ) -> Self {
+        Self {
+            control,
+            deadline,
+            password,
+            operator,
+            confirm_timeout: DEFAULT_CONFIRM_TIMEOUT,
+            confirm_interval: DEFAULT_CONFIRM_INTERVAL,
+        }
+    }
+    pub fn with_confirmation(mut self, timeout: Duration, interval: Duration) -> Self {
+        self.confirm_timeout = timeout;
+        self.confirm_interval = interval;
+        self
+    }
+    /// 传输顺序的 N1~N8：N1 控制类型，N2 保留，N3~N8 截止时间 ssmmhhDDMMYY
+    pub fn block(&self) -> Vec<u8> {
+        let t = &self.deadline;
+        vec![
+            self.control.code(),
+            0x00,
+            to_bcd(t.second),
+            to_bcd(t.minute),
+            to_bcd(t.hour),
+            to_bcd(t.day),
+            to_bcd(t.month),
+            to_bcd((t.year % 100) as u8),
+        ]
+    }
+    pub fn to_pdu(&self, addr: &MeterAddress) -> Result<ProtocolDataUnit, Error> {
+        self.to_pdu_with(addr, &PlainText)
+    }
+    pub fn to_pdu_with<C: CommandCipher + ?Sized>(
+        &self,
+        addr: &MeterAddress,
+        cipher: &C,
+    ) -> Result<ProtocolDataUnit, Error> {
+        let mut block = cipher.encrypt(&self.block())?;
+        block.reverse();
+        ProtocolDataUnit::from_cmd_2(
+            addr.to_vec(),
+            0x1C,
+            &vec![self.password.segment(), self.operator.segment(), block],
+        )
+    }
+    /// 正常应答 9CH，异常应答 DCH
+    pub fn check_reply(reply: &ProtocolDataUnit) -> Result<(), Error> {
+        reply.check_reply(0x1C)
+    }
+}
+
+/// 电表已应答远程控制命令，但在 confirm_timeout 内运行状态字 3 未达到目标状态，
+/// status 为最后一次读到的状态
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControlNotConfirmed {
+    pub addr: MeterAddress,
+    pub control: ControlType,
+    pub status: RelayStatus,
+}
+
+impl fmt::Display for ControlNotConfirmed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "meter `{}` acknowledged {:?} but status word 3 is `{:04x}`",
+            self.addr, self.control, self.status.0
+        )
+    }
+}
+
+impl std::error::Error for ControlNotConfirmed {}
+
+/// 读运行状态字 3
+pub async fn read_relay_status<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+) -> Result<RelayStatus, Error> {
+    match read(transporter, addr, STATUS_WORD_3).await?.as_slice() {
+        [lo, hi] => Ok(RelayStatus(u16::from_le_bytes([*lo, *hi]))),
+        v => Err(format!("invalid status word 3 `{}`", hex::encode(v)).into()),
+    }
+}
+
+/// 发送远程控制命令，然后按 confirm_interval 读运行状态字 3，直到电表已执行或超过
+/// confirm_timeout，返回读到的状态；超时返回 ControlNotConfirmed，包含最后一次读到的状态
+pub async fn remote_control<T: Transporter + ?Sized, C: CommandCipher + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    request: &RemoteControl,
+    cipher: &C,
+) -> Result<RelayStatus, Error> {
+    let adu: Vec<u8> = request.to_pdu_with(addr, cipher)?.into();
+    match transporter.send(&adu).await? {
+        Some(reply) => RemoteControl::check_reply(&reply)?,
+        None => return Err(format!("no response to {:?} of `{}`", request.control, addr).into()),
+    }
+    let deadline = Instant::now() + request.confirm_timeout;
+    loop {
+        let status = read_relay_status(transporter, addr).await?;
+        if request.control.is_done(status) {
+            return Ok(status);
+        }
+        if Instant::now() + request.confirm_interval > deadline {
+            return Err(Box::new(ControlNotConfirmed {
+                addr: *addr,
+                control: request.control,
+                status,
+            }));
+        }
+        tokio::time::sleep(request.confirm_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    fn request(control: ControlType) -> RemoteControl {
+        RemoteControl::new(
+            control,
+            DateTime::new(2023, 2, 15, 10, 20, 30).unwrap(),
+            "02123456".parse().unwrap(),
+            "01020304".parse().unwrap(),
+        )
+        .with_confirmation(Duration::from_millis(30), Duration::from_millis(10))
+    }
+
+    #[test]
+    fn to_pdu() {
+        let addr = ADDR.parse().unwrap();
+        let pdu = request(ControlType::Trip).to_pdu(&addr).unwrap();
+        assert_eq!(pdu.c(), 0x1C);
+        assert_eq!(
+            hex::encode(pdu.payload()),
+            "02563412040302011a00302010150223"
+        );
+    }
+    #[test]
+    fn trip() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            let status =
+                |word: u16| vec![vec![0x04, 0x00, 0x05, 0x03], word.to_be_bytes().to_vec()];
+            // 跳闸延时期间继电器仍为闭合
+            t.reply(ADDR, 0x9C, &[])
+                .reply(ADDR, 0x91, &status(0x0040))
+                .reply(ADDR, 0x91, &status(0x0050));
+            for _ in 0..4 {
+                t.reply(ADDR, 0x91, &status(0x0050));
+            }
+            let addr = ADDR.parse().unwrap();
+            let s = remote_control(&mut t, &addr, &request(ControlType::Trip), &PlainText)
+                .await
+                .unwrap();
+            assert!(s.relay_open());
+            assert_eq!(t.sent.len(), 3);
+            // 电表应答但超时后继电器仍为断开
+            t.reply(ADDR, 0x9C, &[]);
+            t.replies.rotate_right(1);
+            let e = remote_control(
+                &mut t,
+                &addr,
+                &request(ControlType::DirectClose),
+                &PlainText,
+            )
+            .await
+            .unwrap_err();
+            let e = e.downcast_ref::<ControlNotConfirmed>().unwrap();
+            assert_eq!(e.status, RelayStatus(0x0050));
+            assert_eq!(e.control, ControlType::DirectClose);
+            // 超时前多次读运行状态字 3
+            assert!(t.sent.len() >= 6);
+        })
+    }
+}
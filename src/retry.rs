@@ -0,0 +1,359 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+
+use crate::baud::BaudRateControl;
+use crate::error::{Error, Exception};
+use crate::frame::{ChecksumError, DecodeError, ProtocolDataUnit};
+use crate::transporter::{TimeoutError, Transporter};
+
+/// 通信错误类别
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// 发送或接收超时
+    Timeout,
+    /// 应答帧校验码错误
+    Checksum,
+    /// 接收的字节无法解码为帧
+    Decode,
+    /// 电表异常应答，如密码错误，重试不会改变结果
+    Exception,
+    /// 串口、网络读写错误或连接已关闭
+    Io,
+    /// 其他错误
+    Other,
+}
+
+impl ErrorKind {
+    pub fn of(e: &Error) -> Self {
+        if let Some(e) = e.downcast_ref::<RetriesExhausted>() {
+            return Self::of(&e.source);
+        }
+        if e.is::<TimeoutError>() {
+            Self::Timeout
+        } else if e.is::<ChecksumError>() {
+            Self::Checksum
+        } else if e.is::<DecodeError>() {
+            Self::Decode
+        } else if e.is::<Exception>() {
+            Self::Exception
+        } else if e.is::<io::Error>() {
+            Self::Io
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// 两次尝试之间的等待时间
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    Fixed(Duration),
+    /// 每次加倍，不超过 max
+    Exponential {
+        initial: Duration,
+        max: Duration,
+    },
+}
+
+/// 可重复发送的读命令控制码：读数据、读后续数据、读通信地址及 1997 版读数据
+const READ_FUNCTIONS: [u8; 5] = [0x11, 0x12, 0x13, 0x01, 0x02];
+
+/// 重试策略，默认只发送一次
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// 最多发送次数（含第一次）
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    /// 随机抖动比例 0~1，等待时间在 (1 - jitter) ~ 1 倍之间
+    pub jitter: f64,
+    pub retryable: Vec<ErrorKind>,
+    /// 也重试读命令以外的命令。应答丢失时电表可能已执行命令，重发可能重复执行
+    /// （如清零）或因状态已改变而失败（如用旧密码再次修改密码），默认只重试读命令
+    pub retry_commands: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl RetryPolicy {
+    /// 超时、校验码错误及帧无法解码时重试，不等待
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            backoff: Backoff::Fixed(Duration::ZERO),
+            jitter: 0.0,
+            retryable: vec![ErrorKind::Timeout, ErrorKind::Checksum, ErrorKind::Decode],
+            retry_commands: false,
+        }
+    }
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+    /// 异常应答在应答校验时才产生，发送时无法重试，忽略其中的 ErrorKind::Exception
+    pub fn with_retryable(mut self, retryable: Vec<ErrorKind>) -> Self {
+        self.retryable = retryable
+            .into_iter()
+            .filter(|kind| *kind != ErrorKind::Exception)
+            .collect();
+        self
+    }
+    pub fn with_retry_commands(mut self, retry_commands: bool) -> Self {
+        self.retry_commands = retry_commands;
+        self
+    }
+    pub fn is_retryable(&self, e: &Error) -> bool {
+        self.retryable.contains(&ErrorKind::of(e))
+    }
+    /// 是否可按本策略重发 adu
+    pub fn applies_to(&self, adu: &[u8]) -> bool {
+        self.retry_commands
+            || ProtocolDataUnit::try_from(adu.to_vec())
+                .is_ok_and(|pdu| READ_FUNCTIONS.contains(&pdu.function()))
+    }
+    /// 第 attempt 次尝试失败后的等待时间（attempt 从 1 开始）
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Fixed(d) => d,
+            Backoff::Exponential { initial, max } => initial
+                .checked_mul(1 << (attempt.max(1) - 1).min(31))
+                .map_or(max, |d| d.min(max)),
+        };
+        if self.jitter > 0.0 {
+            let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+            delay.mul_f64(1.0 - self.jitter * random)
+        } else {
+            delay
+        }
+    }
+}
+
+/// 超过最多发送次数仍失败，source 为最后一次的错误
+#[derive(Debug)]
+pub struct RetriesExhausted {
+    pub attempts: u32,
+    pub source: Error,
+}
+
+impl fmt::Display for RetriesExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed after {} attempts: {}",
+            self.attempts, self.source
+        )
+    }
+}
+
+impl std::error::Error for RetriesExhausted {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// 按重试策略发送的 Transporter，统计发送次数。默认只重发读命令，见 RetryPolicy::retry_commands
+pub struct Retry<T: Transporter> {
+    inner: T,
+    policy: RetryPolicy,
+    attempts: u32,
+}
+
+impl<T: Transporter> Retry<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            attempts: 0,
+        }
+    }
+    pub fn inner(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+    /// 替换重试策略，返回原策略
+    pub fn set_policy(&mut self, policy: RetryPolicy) -> RetryPolicy {
+        std::mem::replace(&mut self.policy, policy)
+    }
+    /// 上次 reset 以来的发送次数
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[async_trait]
+impl<T: Transporter + Send> Transporter for Retry<T> {
+    async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+        let max_attempts = match self.policy.applies_to(adu) {
+            true => self.policy.max_attempts,
+            false => 1,
+        };
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            self.attempts += 1;
+            match self.inner.send(adu).await {
+                Ok(r) => return Ok(r),
+                Err(e) if attempt < max_attempts && self.policy.is_retryable(&e) => {
+                    tokio::time::sleep(self.policy.delay(attempt)).await;
+                }
+                Err(e) if attempt > 1 => {
+                    return Err(Box::new(RetriesExhausted {
+                        attempts: attempt,
+                        source: e,
+                    }))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    /// 不等待应答的命令无法判断是否成功，不重试
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        self.attempts += 1;
+        self.inner.write(adu).await
+    }
+    async fn open(&mut self) -> Result<(), Error> {
+        self.inner.open().await
+    }
+    async fn close(&mut self) -> Result<(), Error> {
+        self.inner.close().await
+    }
+    async fn flush_input(&mut self) -> Result<usize, Error> {
+        self.inner.flush_input().await
+    }
+    fn begin_transaction(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        self.inner.begin_transaction()
+    }
+    fn end_transaction(&mut self) {
+        self.inner.end_transaction()
+    }
+}
+
+#[async_trait]
+impl<T: BaudRateControl + Send> BaudRateControl for Retry<T> {
+    fn baud_rate(&self) -> Result<u32, Error> {
+        self.inner.baud_rate()
+    }
+    async fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), Error> {
+        self.inner.set_baud_rate(baud_rate).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::{timeout, MockTransporter};
+
+    const ADDR: &str = "202208310002";
+
+    #[test]
+    fn classify() {
+        let e: Error = Box::new(TimeoutError::Frame(Duration::from_secs(1)));
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Timeout);
+        // 不按错误信息分类
+        assert_eq!(ErrorKind::of(&"read timeout".into()), ErrorKind::Other);
+        let e: Error = Box::new(DecodeError("invalid frame".into()));
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Decode);
+        let e: Error = Box::new(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Io);
+        let e: Error = Box::new(ChecksumError {
+            expected: 1,
+            actual: 2,
+        });
+        assert_eq!(ErrorKind::of(&e), ErrorKind::Checksum);
+        assert_eq!(
+            ErrorKind::of(&(Box::new(Exception(0x04)) as Error)),
+            ErrorKind::Exception
+        );
+    }
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::new(5).with_backoff(Backoff::Exponential {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(300),
+        });
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(300));
+        assert_eq!(policy.delay(40), Duration::from_millis(300));
+        let policy = policy.with_jitter(0.5);
+        assert!(policy.delay(1) >= Duration::from_millis(50));
+        assert!(policy.delay(1) <= Duration::from_millis(100));
+    }
+    #[test]
+    fn retry() {
+        block_on(async {
+            let mut mock = MockTransporter::new();
+            mock.replies.push_back(Err(timeout()));
+            mock.reply(ADDR, 0x93, &[hex::decode(ADDR).unwrap()]);
+            let mut t = Retry::new(mock, RetryPolicy::new(3));
+            let adu: Vec<u8> = ProtocolDataUnit::read_addr().unwrap().into();
+            assert!(t.send(&adu).await.is_ok());
+            assert_eq!(t.attempts(), 2);
+            t.reset();
+            let e = t.send(&adu).await.unwrap_err();
+            assert_eq!(e.downcast_ref::<RetriesExhausted>().unwrap().attempts, 3);
+            assert_eq!(ErrorKind::of(&e), ErrorKind::Timeout);
+            // 不可重试的错误直接返回
+            t.set_policy(RetryPolicy::new(3).with_retryable(vec![ErrorKind::Checksum]));
+            t.reset();
+            assert!(t.send(&adu).await.is_err());
+            assert_eq!(t.attempts(), 1);
+            assert_eq!(
+                RetryPolicy::new(3)
+                    .with_retryable(vec![ErrorKind::Timeout, ErrorKind::Exception])
+                    .retryable,
+                [ErrorKind::Timeout]
+            );
+        })
+    }
+    #[test]
+    fn retry_reads_only() {
+        block_on(async {
+            let mut mock = MockTransporter::new();
+            mock.replies.push_back(Err(timeout()));
+            mock.reply(ADDR, 0x98, &[]);
+            let mut t = Retry::new(mock, RetryPolicy::new(3));
+            // 修改密码应答丢失时不重发
+            let adu: Vec<u8> = ProtocolDataUnit::from_cmd_2(
+                hex::decode(ADDR).unwrap(),
+                0x18,
+                &vec![vec![0x04, 0x00, 0x0C, 0x03]],
+            )
+            .unwrap()
+            .into();
+            let e = t.send(&adu).await.unwrap_err();
+            assert_eq!(ErrorKind::of(&e), ErrorKind::Timeout);
+            assert!(e.downcast_ref::<RetriesExhausted>().is_none());
+            assert_eq!(t.attempts(), 1);
+            // 明确允许时重发
+            t.set_policy(RetryPolicy::new(3).with_retry_commands(true));
+            t.reset();
+            t.inner().replies.push_front(Err(timeout()));
+            assert!(t.send(&adu).await.is_ok());
+            assert_eq!(t.attempts(), 2);
+        })
+    }
+}
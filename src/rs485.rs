@@ -1,21 +1,26 @@
 use std::io::{Cursor, Read, Write};
-use std::time::Duration;
 
+use crate::baud::BaudRateControl;
 use crate::error::Error;
 use crate::frame::{Frame, FrameError, ProtocolDataUnit};
-use crate::transporter::Transporter;
+use crate::transporter::{drain, not_open, receive_reply, write_frame, Timeouts, Transporter};
 use async_trait::async_trait;
 use bytes::{Buf, BufMut, BytesMut};
-use futures::stream::StreamExt;
-use futures::SinkExt;
-use tokio::time::timeout;
-use tokio_serial::{self, SerialPort, SerialPortBuilder, SerialPortBuilderExt, SerialStream};
+use tokio_serial::{self, ClearBuffer, SerialPort, SerialPortBuilder, SerialPortBuilderExt, SerialStream};
 use tokio_util::codec::{Decoder, Encoder};
 
+/// DL/T645 缺省通信速率
+const DEFAULT_BAUD_RATE: u32 = 2400;
+
 pub struct RS485Transporter {
     builder: SerialPortBuilder,
     stream: Option<SerialStream>,
-    timeout: Duration,
+    /// 未设置时按波特率计算
+    timeouts: Option<Timeouts>,
+    /// 接收应答时丢弃的不相关帧数
+    stale_frames: u64,
+    /// 已收到但尚未解码的数据，在请求之间保留
+    buf: BytesMut,
 }
 
 pub struct RS485Codec;
@@ -25,9 +30,34 @@ impl RS485Transporter {
         Self {
             builder,
             stream: None,
-            timeout: Duration::from_millis(1),
+            timeouts: None,
+            stale_frames: 0,
+            buf: BytesMut::new(),
         }
     }
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = Some(timeouts);
+        self
+    }
+    /// 设置超时，None 时按波特率计算
+    pub fn set_timeouts(&mut self, timeouts: Option<Timeouts>) {
+        self.timeouts = timeouts;
+    }
+    /// 当前使用的超时，未设置时按串口波特率（未打开时按 2400bps）计算
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts.unwrap_or_else(|| {
+            let baud_rate = self
+                .stream
+                .as_ref()
+                .and_then(|s| s.baud_rate().ok())
+                .unwrap_or(DEFAULT_BAUD_RATE);
+            Timeouts::for_baud_rate(baud_rate)
+        })
+    }
+    /// 接收应答时丢弃的不相关帧数，包括迟到的应答、其他电表的应答及回显的请求帧
+    pub fn stale_frames(&self) -> u64 {
+        self.stale_frames
+    }
 }
 
 impl Encoder<&[u8]> for RS485Codec {
@@ -64,42 +94,70 @@ impl Decoder for RS485Codec {
 #[async_trait]
 impl Transporter for RS485Transporter {
     async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
-        if let Some(stream) = &mut self.stream {
-            stream.set_timeout(self.timeout)?;
-            match timeout(self.timeout, RS485Codec.framed(stream).send(adu)).await {
-                Ok(_) => {}
-                Err(_) => return Err(format!("send timeout after {:?}", self.timeout).into()),
-            }
-        } else {
-            return Err("serial is not opend".into());
-        }
-        let mut reader = RS485Codec.framed(self.stream.as_mut().unwrap());
-        match timeout(self.timeout, reader.next()).await {
-            Ok(r) => {
-                if let Some(r) = r {
-                    match r {
-                        Ok(pdu) => {
-                            return Ok(Some(pdu));
-                        }
-                        Err(e) => {
-                            return Err(e);
-                        }
-                    }
-                }
-            }
-            Err(_) => return Err(format!("read timeout after {:?}", self.timeout).into()),
+        let request = ProtocolDataUnit::try_from(adu.to_vec()).map_err(Into::<Error>::into)?;
+        let timeouts = self.timeouts();
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Err(not_open("serial")),
+        };
+        write_frame(stream, adu, &timeouts).await?;
+        receive_reply(
+            stream,
+            &mut RS485Codec,
+            &mut self.buf,
+            &request,
+            &timeouts,
+            &mut self.stale_frames,
+        )
+        .await
+    }
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        let timeouts = self.timeouts();
+        match &mut self.stream {
+            Some(stream) => write_frame(stream, adu, &timeouts).await,
+            None => Err(not_open("serial")),
         }
-        Ok(None)
     }
     async fn open(&mut self) -> Result<(), Error> {
         let r = self.builder.clone().open_native_async()?;
         self.stream = Some(r);
+        self.buf.clear();
         Ok(())
     }
     async fn close(&mut self) -> Result<(), Error> {
         if let Some(_) = &mut self.stream {
             self.stream = None;
         }
+        self.buf.clear();
+        Ok(())
+    }
+    /// 同时清空串口驱动的接收缓冲区
+    async fn flush_input(&mut self) -> Result<usize, Error> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Err(not_open("serial")),
+        };
+        let n = drain(stream, &mut self.buf).await?;
+        let pending = stream.bytes_to_read()? as usize;
+        stream.clear(ClearBuffer::Input)?;
+        Ok(n + pending)
+    }
+}
+
+#[async_trait]
+impl BaudRateControl for RS485Transporter {
+    fn baud_rate(&self) -> Result<u32, Error> {
+        match &self.stream {
+            Some(stream) => Ok(stream.baud_rate()?),
+            None => Err(not_open("serial")),
+        }
+    }
+    async fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), Error> {
+        self.builder = self.builder.clone().baud_rate(baud_rate);
+        if self.stream.is_some() {
+            self.close().await?;
+            self.open().await?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,344 @@
+use std::time::{Duration, Instant};
+
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::error::{Error, SecurityError};
+use crate::frame::ProtocolDataUnit;
+use crate::password::OperatorCode;
+use crate::remote::CommandCipher;
+use crate::transporter::Transporter;
+
+/// 身份认证
+pub const AUTHENTICATE_DI: DataId = DataId(0x070000FF);
+/// 带 MAC 的明文写数据使用的密码权限
+const PLAIN_WITH_MAC: u8 = 0x99;
+
+/// 主站发起身份认证时的随机数 R1 及其密文 K1，均为传输顺序
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Challenge {
+    pub random: [u8; 8],
+    pub ciphertext: [u8; 8],
+}
+
+/// 身份认证使用的密钥及密码运算，一般由主站加密机或 ESAM 实现。
+/// 所有字节串均为传输顺序。
+pub trait KeyProvider {
+    /// 以分散因子生成随机数 R1 及密文 K1
+    fn challenge(&mut self, factor: &[u8; 8]) -> Result<Challenge, Error>;
+    /// 认证成功后根据电表返回的随机数 R2 及 ESAM 序列号建立会话密钥
+    fn establish(
+        &mut self,
+        challenge: &Challenge,
+        random: &[u8; 4],
+        esam_id: &[u8; 8],
+    ) -> Result<(), Error>;
+    /// 以会话密钥加密
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error>;
+    /// 以会话密钥计算 MAC
+    fn mac(&self, data: &[u8]) -> Result<[u8; 4], Error>;
+}
+
+/// 纯软件实现，密钥以明文保存、算法为简单异或，只用于测试和模拟器，不提供任何安全性
+#[derive(Clone, Debug)]
+pub struct SoftwareKeyProvider {
+    key: [u8; 8],
+    counter: u64,
+    session: Option<[u8; 8]>,
+}
+
+impl SoftwareKeyProvider {
+    pub fn new(key: [u8; 8]) -> Self {
+        Self {
+            key,
+            counter: 0,
+            session: None,
+        }
+    }
+    fn session(&self) -> Result<&[u8; 8], Error> {
+        self.session
+            .as_ref()
+            .ok_or_else(|| "session key is not established".into())
+    }
+}
+
+impl KeyProvider for SoftwareKeyProvider {
+    fn challenge(&mut self, factor: &[u8; 8]) -> Result<Challenge, Error> {
+        self.counter += 1;
+        let random = self.counter.to_le_bytes();
+        let mut ciphertext = [0; 8];
+        for i in 0..8 {
+            ciphertext[i] = random[i] ^ self.key[i] ^ factor[i];
+        }
+        Ok(Challenge { random, ciphertext })
+    }
+    fn establish(
+        &mut self,
+        challenge: &Challenge,
+        random: &[u8; 4],
+        _esam_id: &[u8; 8],
+    ) -> Result<(), Error> {
+        let mut session = [0; 8];
+        for i in 0..8 {
+            session[i] = challenge.random[i] ^ self.key[i] ^ random[i % 4];
+        }
+        self.session = Some(session);
+        Ok(())
+    }
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let session = self.session()?;
+        Ok(data
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b ^ session[i % 8])
+            .collect())
+    }
+    fn mac(&self, data: &[u8]) -> Result<[u8; 4], Error> {
+        let mut mac = [0u8; 4];
+        for (i, b) in self.encrypt(data)?.iter().enumerate() {
+            mac[i % 4] = mac[i % 4].rotate_left(1) ^ b;
+        }
+        Ok(mac)
+    }
+}
+
+/// 安全认证会话（控制码 03H）：身份认证通过后在有效时间内对后续命令加密或附加 MAC
+pub struct SecuritySession<K: KeyProvider> {
+    keys: K,
+    addr: MeterAddress,
+    operator: OperatorCode,
+    /// 身份认证有效时长，与电表参数一致
+    validity: Duration,
+    established: Option<Instant>,
+    esam_id: Option<[u8; 8]>,
+}
+
+impl<K: KeyProvider> SecuritySession<K> {
+    pub fn new(keys: K, addr: MeterAddress, operator: OperatorCode, validity: Duration) -> Self {
+        Self {
+            keys,
+            addr,
+            operator,
+            validity,
+            established: None,
+            esam_id: None,
+        }
+    }
+    pub fn address(&self) -> &MeterAddress {
+        &self.addr
+    }
+    /// 认证时电表返回的 ESAM 序列号
+    pub fn esam_id(&self) -> Option<[u8; 8]> {
+        self.esam_id
+    }
+    /// 剩余有效时间，未认证或已失效时为 None
+    pub fn remaining(&self) -> Option<Duration> {
+        let elapsed = self.established?.elapsed();
+        self.validity.checked_sub(elapsed).filter(|d| !d.is_zero())
+    }
+    pub fn is_valid(&self) -> bool {
+        self.remaining().is_some()
+    }
+    pub fn invalidate(&mut self) {
+        self.established = None;
+    }
+    fn check_valid(&self) -> Result<(), Error> {
+        if !self.is_valid() {
+            return Err(format!("security session of `{}` is not authenticated", self.addr).into());
+        }
+        Ok(())
+    }
+    /// 分散因子：0000 + 表地址
+    fn factor(&self) -> [u8; 8] {
+        let mut factor = [0; 8];
+        factor[2..].copy_from_slice(&self.addr.bytes());
+        factor.reverse();
+        factor
+    }
+    /// 身份认证请求，数据域 DI C0~C3 K1 R1 分散因子
+    pub fn authentication_pdu(&self, challenge: &Challenge) -> Result<ProtocolDataUnit, Error> {
+        ProtocolDataUnit::from_cmd_2(
+            self.addr.to_vec(),
+            0x03,
+            &vec![
+                AUTHENTICATE_DI.bytes().to_vec(),
+                self.operator.segment(),
+                reversed(&challenge.ciphertext),
+                reversed(&challenge.random),
+                reversed(&self.factor()),
+            ],
+        )
+    }
+    /// 执行身份认证，成功后开始计算有效时间
+    pub async fn authenticate<T: Transporter + ?Sized>(
+        &mut self,
+        transporter: &mut T,
+    ) -> Result<(), Error> {
+        self.invalidate();
+        let challenge = self.keys.challenge(&self.factor())?;
+        let adu: Vec<u8> = self.authentication_pdu(&challenge)?.into();
+        let reply = match transporter.send(&adu).await? {
+            Some(reply) => reply,
+            None => return Err(format!("no response authenticating `{}`", self.addr).into()),
+        };
+        // 正常应答 83H，数据域 DI R2 ESAM 序列号；异常应答 C3H，数据域为 SERR 而非 ERR
+        if reply.is_response() && reply.function() == 0x03 && reply.is_abnormal() {
+            return Err(Box::new(SecurityError::from_payload(&reply.payload())));
+        }
+        reply.check_reply(0x03)?;
+        let payload = reply.payload();
+        if payload.len() != 16 || DataId::from_wire(&payload)? != AUTHENTICATE_DI {
+            return Err(format!("invalid authentication reply `{}`", hex::encode(&payload)).into());
+        }
+        let mut random = [0; 4];
+        random.copy_from_slice(&payload[4..8]);
+        let mut esam_id = [0; 8];
+        esam_id.copy_from_slice(&payload[8..16]);
+        self.keys.establish(&challenge, &random, &esam_id)?;
+        self.esam_id = Some(esam_id);
+        self.established = Some(Instant::now());
+        Ok(())
+    }
+    /// 附加 MAC 的写数据请求，数据域 DI 99H 000000 C0~C3 数据 MAC。
+    /// data 各段按高字节在前给出。
+    pub fn write_pdu(&self, di: DataId, data: &[Vec<u8>]) -> Result<ProtocolDataUnit, Error> {
+        self.check_valid()?;
+        let mut segments = vec![
+            di.bytes().to_vec(),
+            vec![0x00, 0x00, 0x00, PLAIN_WITH_MAC],
+            self.operator.segment(),
+        ];
+        segments.extend(data.iter().cloned());
+        let wire = segments
+            .iter()
+            .flat_map(|s| s.iter().rev())
+            .copied()
+            .collect::<Vec<_>>();
+        segments.push(reversed(&self.keys.mac(&wire)?));
+        ProtocolDataUnit::from_cmd_2(self.addr.to_vec(), 0x14, &segments)
+    }
+    /// 在会话内写数据
+    pub async fn write<T: Transporter + ?Sized>(
+        &self,
+        transporter: &mut T,
+        di: DataId,
+        data: &[Vec<u8>],
+    ) -> Result<(), Error> {
+        let adu: Vec<u8> = self.write_pdu(di, data)?.into();
+        match transporter.send(&adu).await? {
+            Some(reply) => reply.check_reply(0x14),
+            None => Err(format!("no response writing `{}`", di).into()),
+        }
+    }
+}
+
+/// 远程控制命令块按会话密钥加密
+impl<K: KeyProvider> CommandCipher for SecuritySession<K> {
+    fn encrypt(&self, block: &[u8]) -> Result<Vec<u8>, Error> {
+        self.check_valid()?;
+        self.keys.encrypt(block)
+    }
+}
+
+fn reversed(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().rev().copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::datetime::DateTime;
+    use crate::mock::MockTransporter;
+    use crate::remote::{ControlType, RemoteControl};
+
+    const ADDR: &str = "202208310002";
+
+    fn session(validity: Duration) -> SecuritySession<SoftwareKeyProvider> {
+        SecuritySession::new(
+            SoftwareKeyProvider::new([1, 2, 3, 4, 5, 6, 7, 8]),
+            ADDR.parse().unwrap(),
+            "01020304".parse().unwrap(),
+            validity,
+        )
+    }
+
+    fn accept(t: &mut MockTransporter) {
+        t.reply(
+            ADDR,
+            0x83,
+            &[
+                AUTHENTICATE_DI.bytes().to_vec(),
+                vec![0x11, 0x22, 0x33, 0x44],
+                vec![0, 0, 0, 0, 0, 0, 0, 1],
+            ],
+        );
+    }
+
+    #[test]
+    fn authenticate() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            accept(&mut t);
+            t.reply(ADDR, 0xC3, &[vec![0x00, 0x04]]);
+            let mut s = session(Duration::from_secs(300));
+            assert!(s.write_pdu(DataId(0x04000103), &[]).is_err());
+            s.authenticate(&mut t).await.unwrap();
+            assert!(s.is_valid());
+            assert_eq!(s.esam_id(), Some([1, 0, 0, 0, 0, 0, 0, 0]));
+            let sent = t.sent[0].payload();
+            assert_eq!(t.sent[0].c(), 0x03);
+            assert_eq!(sent.len(), 32);
+            // 分散因子为 0000 + 表地址
+            assert_eq!(hex::encode(&sent[24..]), "0200310822200000");
+            let e = s.authenticate(&mut t).await.unwrap_err();
+            let e = e.downcast_ref::<SecurityError>().unwrap();
+            assert_eq!(*e, SecurityError(0x0004));
+            assert!(e.esam_verification_failed());
+            assert_eq!(
+                e.to_string(),
+                "security exception `0004`: ESAM verification failed"
+            );
+            assert!(!s.is_valid());
+            // SERR 高字节
+            t.reply(ADDR, 0xC3, &[vec![0x01, 0x00]]);
+            let e = s.authenticate(&mut t).await.unwrap_err();
+            assert!(e.downcast_ref::<SecurityError>().unwrap().suspended());
+        })
+    }
+    #[test]
+    fn protected_commands() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            accept(&mut t);
+            let mut s = session(Duration::from_secs(300));
+            s.authenticate(&mut t).await.unwrap();
+            let pdu = s.write_pdu(DataId(0x04000103), &[vec![0x15]]).unwrap();
+            let payload = pdu.payload();
+            assert_eq!(hex::encode(&payload[..13]), "03010004990000000403020115");
+            assert_eq!(payload.len(), 17);
+            let control = RemoteControl::new(
+                ControlType::Trip,
+                DateTime::new(2023, 2, 15, 10, 20, 30).unwrap(),
+                "02123456".parse().unwrap(),
+                "01020304".parse().unwrap(),
+            );
+            let encrypted = control.to_pdu_with(s.address(), &s).unwrap();
+            let plain = control.to_pdu(s.address()).unwrap();
+            assert_ne!(encrypted.payload()[8..], plain.payload()[8..]);
+            s.invalidate();
+            assert!(control.to_pdu_with(s.address(), &s).is_err());
+        })
+    }
+    #[test]
+    fn expired() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            accept(&mut t);
+            let mut s = session(Duration::ZERO);
+            s.authenticate(&mut t).await.unwrap();
+            assert!(!s.is_valid());
+            assert!(s.write_pdu(DataId(0x04000103), &[]).is_err());
+        })
+    }
+}
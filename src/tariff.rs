@@ -0,0 +1,779 @@
+use std::fmt;
+
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::datetime::{days_in_month, from_bcd, to_bcd, DateTime};
+use crate::error::Error;
+use crate::password::{OperatorCode, Password};
+use crate::read::read;
+use crate::transporter::Transporter;
+use crate::write::{write, Write};
+
+/// 年时区数 p
+pub const YEAR_ZONES: DataId = DataId(0x04000201);
+/// 日时段表数 q
+pub const DAY_TABLES: DataId = DataId(0x04000202);
+/// 日时段数 m
+pub const DAY_SEGMENTS: DataId = DataId(0x04000203);
+/// 费率数 k
+pub const TARIFFS: DataId = DataId(0x04000204);
+/// 两套时区表切换时间
+pub const ZONE_SWITCH_TIME: DataId = DataId(0x04000106);
+/// 两套日时段表切换时间
+pub const SEGMENT_SWITCH_TIME: DataId = DataId(0x04000107);
+/// 协议允许的最大年时区数
+pub const MAX_YEAR_ZONES: u8 = 14;
+/// 协议允许的最大日时段表数
+pub const MAX_DAY_TABLES: u8 = 8;
+/// 协议允许的每个日时段表的最大时段数
+pub const MAX_DAY_SEGMENTS: u8 = 14;
+
+/// 年时区起始日期及日时段表号 MMDDNN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct YearZone {
+    pub month: u8,
+    pub day: u8,
+    pub table: u8,
+}
+
+/// 日时段起始时间及费率号 hhmmNN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeSegment {
+    pub hour: u8,
+    pub minute: u8,
+    pub tariff: u8,
+}
+
+/// 电表配置的时区、时段、费率数
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TariffLimits {
+    pub year_zones: u8,
+    pub day_tables: u8,
+    pub day_segments: u8,
+    pub tariffs: u8,
+}
+
+/// 第一套（0401，当前套）或第二套（0402，备用套）时区表和日时段表，
+/// 到达切换时间后电表启用第二套
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleSet {
+    First,
+    Second,
+}
+
+impl ScheduleSet {
+    /// 时区表数据标识
+    pub fn zones_id(&self) -> DataId {
+        DataId::new(0x04, self.di2(), 0x00, 0x00)
+    }
+    /// 第 n（1~8）日时段表数据标识
+    pub fn table_id(&self, n: u8) -> DataId {
+        DataId::new(0x04, self.di2(), 0x00, n)
+    }
+    fn di2(&self) -> u8 {
+        match self {
+            Self::First => 0x01,
+            Self::Second => 0x02,
+        }
+    }
+}
+
+/// 一套费率时段：年时区表及各日时段表（tables[0] 为第 1 日时段表）
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TariffSchedule {
+    pub zones: Vec<YearZone>,
+    pub tables: Vec<Vec<TimeSegment>>,
+}
+
+/// 两套费率时段之间的差异
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScheduleChange {
+    Zones {
+        old: Vec<YearZone>,
+        new: Vec<YearZone>,
+    },
+    /// number 从 1 开始，None 表示该日时段表不存在
+    Table {
+        number: u8,
+        old: Option<Vec<TimeSegment>>,
+        new: Option<Vec<TimeSegment>>,
+    },
+}
+
+/// 写费率时段的步骤
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleStep {
+    Limits,
+    /// 读 set 中现有的费率时段
+    Current,
+    Zones,
+    Table(u8),
+    SwitchTime(DataId),
+}
+
+impl fmt::Display for ScheduleStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Limits => write!(f, "limits"),
+            Self::Current => write!(f, "current schedule"),
+            Self::Zones => write!(f, "year zones"),
+            Self::Table(n) => write!(f, "day table {}", n),
+            Self::SwitchTime(id) => write!(f, "switch time `{}`", id),
+        }
+    }
+}
+
+/// 写费率时段失败，step 为失败的步骤，之前的步骤均已写入成功
+#[derive(Debug)]
+pub struct ScheduleWriteError {
+    pub step: ScheduleStep,
+    pub source: Error,
+}
+
+impl fmt::Display for ScheduleWriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "write {} failed: {}", self.step, self.source)
+    }
+}
+
+impl std::error::Error for ScheduleWriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl TariffSchedule {
+    /// 检查时区数、日时段表数、时段数和费率号是否超出电表配置及协议限制
+    pub fn validate(&self, limits: &TariffLimits) -> Result<(), Error> {
+        let year_zones = limits.year_zones.min(MAX_YEAR_ZONES);
+        let day_tables = limits.day_tables.min(MAX_DAY_TABLES);
+        let day_segments = limits.day_segments.min(MAX_DAY_SEGMENTS);
+        if self.zones.is_empty() || self.zones.len() > year_zones as usize {
+            return Err(format!(
+                "{} year zones, meter allows 1~{}",
+                self.zones.len(),
+                year_zones
+            )
+            .into());
+        }
+        if self.tables.is_empty() || self.tables.len() > day_tables as usize {
+            return Err(format!(
+                "{} day tables, meter allows 1~{}",
+                self.tables.len(),
+                day_tables
+            )
+            .into());
+        }
+        for z in self.zones.iter() {
+            // 年时区每年适用，2 月按闰年允许 29 日
+            if !(1..=12).contains(&z.month) || z.day == 0 || z.day > days_in_month(2000, z.month) {
+                return Err(format!("invalid year zone start {:02}-{:02}", z.month, z.day).into());
+            }
+            if z.table == 0 || z.table as usize > self.tables.len() {
+                return Err(format!("year zone refers to missing day table {}", z.table).into());
+            }
+        }
+        for (i, table) in self.tables.iter().enumerate() {
+            if table.is_empty() || table.len() > day_segments as usize {
+                return Err(format!(
+                    "day table {} has {} segments, meter allows 1~{}",
+                    i + 1,
+                    table.len(),
+                    day_segments
+                )
+                .into());
+            }
+            for s in table.iter() {
+                if s.hour > 23 || s.minute > 59 {
+                    return Err(format!(
+                        "day table {} has invalid time {:02}:{:02}",
+                        i + 1,
+                        s.hour,
+                        s.minute
+                    )
+                    .into());
+                }
+                if s.tariff == 0 || s.tariff > limits.tariffs {
+                    return Err(format!(
+                        "day table {} uses tariff {}, meter has {}",
+                        i + 1,
+                        s.tariff,
+                        limits.tariffs
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+    /// 与 other 比较，返回由 self 变为 other 的差异
+    pub fn diff(&self, other: &Self) -> Vec<ScheduleChange> {
+        let mut changes = vec![];
+        if self.zones != other.zones {
+            changes.push(ScheduleChange::Zones {
+                old: self.zones.clone(),
+                new: other.zones.clone(),
+            });
+        }
+        for i in 0..self.tables.len().max(other.tables.len()) {
+            let (old, new) = (self.tables.get(i), other.tables.get(i));
+            if old != new {
+                changes.push(ScheduleChange::Table {
+                    number: i as u8 + 1,
+                    old: old.cloned(),
+                    new: new.cloned(),
+                });
+            }
+        }
+        changes
+    }
+    /// 按电表配置的时区数和时段数重复最后一项补足，电表读出的费率时段总是补足后的长度，
+    /// 写入不足的项时电表保留原有的后续项
+    pub fn padded(&self, limits: &TariffLimits) -> Self {
+        fn pad<E: Copy>(entries: &[E], len: u8) -> Vec<E> {
+            let mut entries = entries.to_vec();
+            if let Some(last) = entries.last().copied() {
+                entries.resize(entries.len().max(len as usize), last);
+            }
+            entries
+        }
+        Self {
+            zones: pad(&self.zones, limits.year_zones),
+            tables: self
+                .tables
+                .iter()
+                .map(|table| pad(table, limits.day_segments))
+                .collect(),
+        }
+    }
+    /// 写入 set 所需的数据标识和数据，各字段高字节在前，写入前须先经 validate 检查并 padded 补足
+    pub fn write_data(&self, set: ScheduleSet) -> Vec<(ScheduleStep, DataId, Vec<Vec<u8>>)> {
+        let mut writes = vec![];
+        let zones = self
+            .zones
+            .iter()
+            .map(|z| vec![to_bcd(z.month), to_bcd(z.day), to_bcd(z.table)])
+            .collect();
+        writes.push((ScheduleStep::Zones, set.zones_id(), zones));
+        for (i, table) in self.tables.iter().enumerate() {
+            let n = i as u8 + 1;
+            let segments = table
+                .iter()
+                .map(|s| vec![to_bcd(s.hour), to_bcd(s.minute), to_bcd(s.tariff)])
+                .collect();
+            writes.push((ScheduleStep::Table(n), set.table_id(n), segments));
+        }
+        writes
+    }
+}
+
+fn decode_nn(data: &[u8]) -> Result<u8, Error> {
+    match data {
+        [b] => from_bcd(*b),
+        _ => Err(format!("invalid NN `{}`", hex::encode(data)).into()),
+    }
+}
+
+/// 解析 MMDDNN / hhmmNN 列表（已减 33H，每项低字节在前），只取前 count 项
+fn decode_triples(data: &[u8], count: usize) -> Result<Vec<[u8; 3]>, Error> {
+    if !data.len().is_multiple_of(3) || data.len() / 3 < count {
+        return Err(format!("expect {} entries, got `{}`", count, hex::encode(data)).into());
+    }
+    data.chunks(3)
+        .take(count)
+        .map(|c| Ok([from_bcd(c[2])?, from_bcd(c[1])?, from_bcd(c[0])?]))
+        .collect()
+}
+
+/// 读电表配置的时区、时段、费率数
+pub async fn read_limits<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+) -> Result<TariffLimits, Error> {
+    Ok(TariffLimits {
+        year_zones: decode_nn(&read(transporter, addr, YEAR_ZONES).await?)?,
+        day_tables: decode_nn(&read(transporter, addr, DAY_TABLES).await?)?,
+        day_segments: decode_nn(&read(transporter, addr, DAY_SEGMENTS).await?)?,
+        tariffs: decode_nn(&read(transporter, addr, TARIFFS).await?)?,
+    })
+}
+
+/// 读一套费率时段，按电表配置的时区数、日时段表数和时段数截取
+pub async fn read_schedule<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    set: ScheduleSet,
+) -> Result<TariffSchedule, Error> {
+    let limits = read_limits(transporter, addr).await?;
+    read_set(transporter, addr, set, &limits).await
+}
+
+async fn read_set<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    set: ScheduleSet,
+    limits: &TariffLimits,
+) -> Result<TariffSchedule, Error> {
+    let data = read(transporter, addr, set.zones_id()).await?;
+    let zones = decode_triples(&data, limits.year_zones as usize)?
+        .into_iter()
+        .map(|[month, day, table]| YearZone { month, day, table })
+        .collect();
+    let mut tables = vec![];
+    for n in 1..=limits.day_tables {
+        let data = read(transporter, addr, set.table_id(n)).await?;
+        let table = decode_triples(&data, limits.day_segments as usize)?
+            .into_iter()
+            .map(|[hour, minute, tariff]| TimeSegment {
+                hour,
+                minute,
+                tariff,
+            })
+            .collect();
+        tables.push(table);
+    }
+    Ok(TariffSchedule { zones, tables })
+}
+
+/// 读两套时区表切换时间和两套日时段表切换时间
+pub async fn read_switch_times<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+) -> Result<(DateTime, DateTime), Error> {
+    let zone = DateTime::from_yymmddhhmm(&read(transporter, addr, ZONE_SWITCH_TIME).await?)?;
+    let segment = DateTime::from_yymmddhhmm(&read(transporter, addr, SEGMENT_SWITCH_TIME).await?)?;
+    Ok((zone, segment))
+}
+
+/// 按电表配置补足时区和时段后，读取 set 中现有的费率时段，只写入有变化的时区表和日时段表。
+/// set 为备用套时，再按变化写入两套时区表或日时段表的切换时间；写当前套时不写切换时间，
+/// 忽略 switch_time。读写期间独占共享总线。失败时返回 ScheduleWriteError，指明失败的步骤。
+pub async fn write_schedule<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    set: ScheduleSet,
+    schedule: &TariffSchedule,
+    switch_time: &DateTime,
+    password: &Password,
+    operator: &OperatorCode,
+) -> Result<(), Error> {
+    transporter.begin_transaction().await?;
+    let r = write_changes(
+        transporter,
+        addr,
+        set,
+        schedule,
+        switch_time,
+        password,
+        operator,
+    )
+    .await;
+    transporter.end_transaction();
+    r
+}
+
+/// 按差异写费率时段，调用方已开始多帧交互
+async fn write_changes<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    set: ScheduleSet,
+    schedule: &TariffSchedule,
+    switch_time: &DateTime,
+    password: &Password,
+    operator: &OperatorCode,
+) -> Result<(), Error> {
+    let fail = |step: ScheduleStep| {
+        move |source: Error| -> Error { Box::new(ScheduleWriteError { step, source }) }
+    };
+    let limits = read_limits(transporter, addr)
+        .await
+        .map_err(fail(ScheduleStep::Limits))?;
+    schedule
+        .validate(&limits)
+        .map_err(fail(ScheduleStep::Limits))?;
+    let schedule = &schedule.padded(&limits);
+    let current = read_set(transporter, addr, set, &limits)
+        .await
+        .map_err(fail(ScheduleStep::Current))?;
+    let changes = current.diff(schedule);
+    let zones_changed = changes
+        .iter()
+        .any(|c| matches!(c, ScheduleChange::Zones { .. }));
+    // 电表的日时段表数固定，新费率时段中没有的日时段表保持不变
+    let tables: Vec<u8> = changes
+        .iter()
+        .filter_map(|c| match c {
+            ScheduleChange::Table {
+                number,
+                new: Some(_),
+                ..
+            } => Some(*number),
+            _ => None,
+        })
+        .collect();
+    let mut writes: Vec<_> = schedule
+        .write_data(set)
+        .into_iter()
+        .filter(|(step, _, _)| match step {
+            ScheduleStep::Zones => zones_changed,
+            ScheduleStep::Table(n) => tables.contains(n),
+            _ => true,
+        })
+        .collect();
+    if set == ScheduleSet::Second {
+        let switches = [
+            (ZONE_SWITCH_TIME, zones_changed),
+            (SEGMENT_SWITCH_TIME, !tables.is_empty()),
+        ];
+        for (id, changed) in switches {
+            if changed {
+                writes.push((
+                    ScheduleStep::SwitchTime(id),
+                    id,
+                    vec![switch_time.to_yymmddhhmm()],
+                ));
+            }
+        }
+    }
+    for (step, id, data) in writes {
+        let request = Write::new(id, *password, *operator, data);
+        write(transporter, addr, &request)
+            .await
+            .map_err(fail(step))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    fn schedule() -> TariffSchedule {
+        TariffSchedule {
+            zones: vec![
+                YearZone {
+                    month: 1,
+                    day: 1,
+                    table: 1,
+                },
+                YearZone {
+                    month: 6,
+                    day: 1,
+                    table: 2,
+                },
+            ],
+            tables: vec![
+                vec![
+                    TimeSegment {
+                        hour: 0,
+                        minute: 0,
+                        tariff: 4,
+                    },
+                    TimeSegment {
+                        hour: 8,
+                        minute: 0,
+                        tariff: 2,
+                    },
+                ],
+                vec![TimeSegment {
+                    hour: 0,
+                    minute: 0,
+                    tariff: 3,
+                }],
+            ],
+        }
+    }
+
+    fn limits(t: &mut MockTransporter) {
+        for (id, v) in [
+            (0x04000201u32, 2u8),
+            (0x04000202, 2),
+            (0x04000203, 2),
+            (0x04000204, 4),
+        ] {
+            t.reply(ADDR, 0x91, &[id.to_be_bytes().to_vec(), vec![v]]);
+        }
+    }
+
+    /// set 中现有的费率时段：第 2 日时段表补足 2 个时段
+    fn current() -> TariffSchedule {
+        let mut s = schedule();
+        s.tables[1].push(TimeSegment {
+            hour: 0,
+            minute: 0,
+            tariff: 3,
+        });
+        s
+    }
+
+    /// 按 write_data 的格式应答读 set 的请求
+    fn reply_schedule(t: &mut MockTransporter, set: ScheduleSet, s: &TariffSchedule) {
+        for (_, id, data) in s.write_data(set) {
+            let mut segments = vec![id.0.to_be_bytes().to_vec()];
+            segments.extend(data);
+            t.reply(ADDR, 0x91, &segments);
+        }
+    }
+
+    /// 各写请求的数据标识
+    fn written(t: &MockTransporter) -> Vec<DataId> {
+        t.sent
+            .iter()
+            .filter(|pdu| pdu.function() == 0x14)
+            .map(|pdu| {
+                let p = pdu.payload();
+                DataId(u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn validate_and_diff() {
+        let limits = TariffLimits {
+            year_zones: 2,
+            day_tables: 2,
+            day_segments: 2,
+            tariffs: 4,
+        };
+        let s = schedule();
+        assert!(s.validate(&limits).is_ok());
+        let mut other = s.clone();
+        other.tables[1][0].tariff = 5;
+        assert!(other.validate(&limits).is_err());
+        other.tables.push(vec![]);
+        assert_eq!(
+            s.diff(&other)
+                .iter()
+                .map(|c| match c {
+                    ScheduleChange::Table { number, .. } => *number,
+                    ScheduleChange::Zones { .. } => 0,
+                })
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+    }
+    #[test]
+    fn zone_dates_and_padding() {
+        let limits = TariffLimits {
+            year_zones: 3,
+            day_tables: 2,
+            day_segments: 2,
+            tariffs: 4,
+        };
+        let mut s = schedule();
+        s.zones[1].month = 2;
+        s.zones[1].day = 29;
+        assert!(s.validate(&limits).is_ok());
+        s.zones[1].day = 30;
+        let e = s.validate(&limits).unwrap_err();
+        assert_eq!(e.to_string(), "invalid year zone start 02-30");
+        s.zones[1].month = 4;
+        s.zones[1].day = 31;
+        assert!(s.validate(&limits).is_err());
+        let padded = schedule().padded(&limits);
+        assert_eq!(padded.zones.len(), 3);
+        assert_eq!(padded.zones[2], padded.zones[1]);
+        assert_eq!(padded.tables, current().tables);
+    }
+    #[test]
+    fn protocol_limits() {
+        // 电表配置超出协议限制时按协议限制检查
+        let limits = TariffLimits {
+            year_zones: 20,
+            day_tables: 10,
+            day_segments: 20,
+            tariffs: 4,
+        };
+        let segment = TimeSegment {
+            hour: 0,
+            minute: 0,
+            tariff: 1,
+        };
+        let zone = YearZone {
+            month: 1,
+            day: 1,
+            table: 1,
+        };
+        let s = TariffSchedule {
+            zones: vec![zone; 14],
+            tables: vec![vec![segment; 14]; 8],
+        };
+        assert!(s.validate(&limits).is_ok());
+        let mut other = s.clone();
+        other.tables.push(vec![segment]);
+        let e = other.validate(&limits).unwrap_err();
+        assert_eq!(e.to_string(), "9 day tables, meter allows 1~8");
+        let mut other = s.clone();
+        other.tables[0].push(segment);
+        let e = other.validate(&limits).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "day table 1 has 15 segments, meter allows 1~14"
+        );
+        let mut other = s;
+        other.zones.push(zone);
+        assert!(other.validate(&limits).is_err());
+    }
+    #[test]
+    fn read_schedule() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            limits(&mut t);
+            // 电表返回 3 个时区，只取前 2 个
+            t.reply(
+                ADDR,
+                0x91,
+                &[
+                    vec![0x04, 0x01, 0x00, 0x00],
+                    vec![0x01, 0x01, 0x01],
+                    vec![0x06, 0x01, 0x02],
+                    vec![0x06, 0x01, 0x02],
+                ],
+            );
+            t.reply(
+                ADDR,
+                0x91,
+                &[
+                    vec![0x04, 0x01, 0x00, 0x01],
+                    vec![0x00, 0x00, 0x04],
+                    vec![0x08, 0x00, 0x02],
+                ],
+            );
+            t.reply(
+                ADDR,
+                0x91,
+                &[
+                    vec![0x04, 0x01, 0x00, 0x02],
+                    vec![0x00, 0x00, 0x03],
+                    vec![0x00, 0x00, 0x03],
+                ],
+            );
+            let addr = ADDR.parse().unwrap();
+            let s = super::read_schedule(&mut t, &addr, ScheduleSet::First)
+                .await
+                .unwrap();
+            let mut expect = schedule();
+            expect.tables[1].push(TimeSegment {
+                hour: 0,
+                minute: 0,
+                tariff: 3,
+            });
+            assert_eq!(s, expect);
+        })
+    }
+    #[test]
+    fn write_schedule_failed_table() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            limits(&mut t);
+            let mut old = current();
+            old.zones[1].day = 2;
+            old.tables[1][1].tariff = 2;
+            reply_schedule(&mut t, ScheduleSet::Second, &old);
+            t.reply(ADDR, 0x94, &[]);
+            t.reply(ADDR, 0xD4, &[vec![0x20]]);
+            let addr = ADDR.parse().unwrap();
+            let switch_time = DateTime::new(2023, 3, 1, 0, 0, 0).unwrap();
+            let e = super::write_schedule(
+                &mut t,
+                &addr,
+                ScheduleSet::Second,
+                &schedule(),
+                &switch_time,
+                &"02000000".parse().unwrap(),
+                &OperatorCode::default(),
+            )
+            .await
+            .unwrap_err();
+            let e = e.downcast_ref::<ScheduleWriteError>().unwrap();
+            assert_eq!(e.step, ScheduleStep::Table(2));
+            // 第 2 日时段表：DI + 密码 + 操作者代码 + 按 m=2 重复最后一项补足的 2 个时段
+            assert_eq!(
+                hex::encode(t.sent[8].payload()),
+                "020002040200000000000000030000030000"
+            );
+            // 第 1 日时段表未变化，不写入
+            assert_eq!(
+                written(&t),
+                [
+                    ScheduleSet::Second.zones_id(),
+                    ScheduleSet::Second.table_id(2)
+                ]
+            );
+        })
+    }
+    #[test]
+    fn write_schedule_switch_times() {
+        block_on(async {
+            let addr = ADDR.parse().unwrap();
+            let switch_time = DateTime::new(2023, 3, 1, 0, 0, 0).unwrap();
+            let password = "02000000".parse().unwrap();
+            let operator = OperatorCode::default();
+
+            // 备用套只有日时段表变化，只写日时段表切换时间
+            let mut t = MockTransporter::new();
+            limits(&mut t);
+            reply_schedule(&mut t, ScheduleSet::Second, &current());
+            t.reply(ADDR, 0x94, &[]).reply(ADDR, 0x94, &[]);
+            let mut new = current();
+            new.tables[0][1].tariff = 3;
+            super::write_schedule(
+                &mut t,
+                &addr,
+                ScheduleSet::Second,
+                &new,
+                &switch_time,
+                &password,
+                &operator,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                written(&t),
+                [ScheduleSet::Second.table_id(1), SEGMENT_SWITCH_TIME]
+            );
+
+            // 当前套不写切换时间
+            let mut t = MockTransporter::new();
+            limits(&mut t);
+            reply_schedule(&mut t, ScheduleSet::First, &current());
+            t.reply(ADDR, 0x94, &[]);
+            let mut new = current();
+            new.zones[1].month = 7;
+            super::write_schedule(
+                &mut t,
+                &addr,
+                ScheduleSet::First,
+                &new,
+                &switch_time,
+                &password,
+                &operator,
+            )
+            .await
+            .unwrap();
+            assert_eq!(written(&t), [ScheduleSet::First.zones_id()]);
+
+            // 补足后没有变化时不写入
+            let mut t = MockTransporter::new();
+            limits(&mut t);
+            reply_schedule(&mut t, ScheduleSet::Second, &current());
+            super::write_schedule(
+                &mut t,
+                &addr,
+                ScheduleSet::Second,
+                &schedule(),
+                &switch_time,
+                &password,
+                &operator,
+            )
+            .await
+            .unwrap();
+            assert!(written(&t).is_empty());
+        })
+    }
+}
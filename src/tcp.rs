@@ -3,18 +3,20 @@ use std::time::Duration;
 
 use crate::error::Error;
 use crate::frame::{Frame, FrameError, ProtocolDataUnit};
-use crate::transporter::Transporter;
+use crate::transporter::{drain, not_open, receive_reply, write_frame, TimeoutError, Timeouts, Transporter};
 use async_trait::async_trait;
 use bytes::{Buf, BufMut, BytesMut};
-use futures::stream::StreamExt;
-use futures::SinkExt;
 use tokio::net::{TcpStream};
 use tokio::time::timeout;
 use tokio_util::codec::{Decoder, Encoder};
 
 pub struct TcpTransporter {
     addr: String,
-    timeout: Duration,
+    timeouts: Timeouts,
+    /// 接收应答时丢弃的不相关帧数
+    stale_frames: u64,
+    /// 已收到但尚未解码的数据，在请求之间保留
+    buf: BytesMut,
     stream: Option<TcpStream>,
 }
 
@@ -24,10 +26,26 @@ impl TcpTransporter {
     pub fn new(addr: &str) -> Self {
         Self {
             addr: addr.to_string(),
-            timeout: Duration::from_secs(1),
+            timeouts: Timeouts::fixed(Duration::from_secs(1)),
             stream: None,
+            stale_frames: 0,
+            buf: BytesMut::new(),
         }
     }
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+    /// 接收应答时丢弃的不相关帧数，包括迟到的应答及其他电表的应答
+    pub fn stale_frames(&self) -> u64 {
+        self.stale_frames
+    }
 }
 
 impl Encoder<&[u8]> for TcpCodec {
@@ -64,48 +82,52 @@ impl Decoder for TcpCodec {
 #[async_trait]
 impl Transporter for TcpTransporter {
     async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
-        if let Some(stream) = &mut self.stream {
-            match timeout(self.timeout, TcpCodec.framed(stream).send(adu)).await {
-              Ok(_) => {}
-              Err(_) => return Err(format!("send timeout after {:?}", self.timeout).into()),
-          }
-        } else {
-            return Err("serial is not opend".into());
-        }
-        let mut reader = TcpCodec.framed(self.stream.as_mut().unwrap());
-        match timeout(self.timeout, reader.next()).await {
-            Ok(r) => {
-                if let Some(r) = r {
-                    match r {
-                        Ok(pdu) => {
-                            return Ok(Some(pdu));
-                        }
-                        Err(e) => {
-                            return Err(e);
-                        }
-                    }
-                }
-            }
-            Err(_) => return Err(format!("read timeout after {:?}", self.timeout).into()),
+        let request = ProtocolDataUnit::try_from(adu.to_vec()).map_err(Into::<Error>::into)?;
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return Err(not_open("tcp")),
+        };
+        write_frame(stream, adu, &self.timeouts).await?;
+        receive_reply(
+            stream,
+            &mut TcpCodec,
+            &mut self.buf,
+            &request,
+            &self.timeouts,
+            &mut self.stale_frames,
+        )
+        .await
+    }
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        match &mut self.stream {
+            Some(stream) => write_frame(stream, adu, &self.timeouts).await,
+            None => Err(not_open("tcp")),
         }
-        Ok(None)
     }
     async fn open(&mut self) -> Result<(), Error> {
-      match timeout(self.timeout, TcpStream::connect(&self.addr)).await {
+      match timeout(self.timeouts.connect, TcpStream::connect(&self.addr)).await {
           Ok(Ok(stream)) => {
             self.stream = Some(stream);
+            self.buf.clear();
             Ok(())
           },
-          Ok(Err(e)) => return Err(format!("connection error: {}", e).into()),
-          Err(_) => return Err(format!("connection timed out after {:?}", self.timeout).into()),
+          Ok(Err(e)) => return Err(Box::new(e)),
+          Err(_) => return Err(Box::new(TimeoutError::Connect(self.timeouts.connect))),
       }
     }
     async fn close(&mut self) -> Result<(), Error> {
         if let Some(_) = &mut self.stream {
             self.stream = None;
         }
+        self.buf.clear();
         Ok(())
     }
+    async fn flush_input(&mut self) -> Result<usize, Error> {
+        match &mut self.stream {
+            Some(stream) => drain(stream, &mut self.buf).await,
+            None => Err(not_open("tcp")),
+        }
+    }
 }
 
 
@@ -115,6 +137,8 @@ mod tests {
 
     use tokio_test::block_on;
 
+    use tokio::io::AsyncWriteExt;
+
     use crate::frame::ProtocolDataUnit;
 
     use super::*;
@@ -144,4 +168,49 @@ mod tests {
             }
         })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn keep_buffered_frames() {
+        block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            let mut tcp = TcpTransporter::new(&addr);
+            tcp.open().await.unwrap();
+            let (mut meter, _) = listener.accept().await.unwrap();
+            let request = |c: u8, di: u8| -> Vec<u8> {
+                ProtocolDataUnit::from_cmd_2(
+                    hex::decode("202208310002").unwrap(),
+                    c,
+                    &vec![vec![0x00, di, 0x00, 0x00]],
+                )
+                .unwrap()
+                .into()
+            };
+            // 两帧应答一次到达，第二帧留在缓冲区中供下一次请求使用
+            let mut replies = request(0x91, 0x01);
+            replies.extend(request(0x91, 0x02));
+            meter.write_all(&replies).await.unwrap();
+            let first = tcp.send(&request(0x11, 0x01)).await.unwrap().unwrap();
+            assert_eq!(first.payload()[..4], [0x00, 0x00, 0x01, 0x00]);
+            let second = tcp.send(&request(0x11, 0x02)).await.unwrap().unwrap();
+            assert_eq!(second.payload()[..4], [0x00, 0x00, 0x02, 0x00]);
+            meter.write_all(&[0x00, 0x01, 0x02]).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(tcp.flush_input().await.unwrap(), 3);
+            assert_eq!(tcp.flush_input().await.unwrap(), 0);
+        })
+    }
+
+    #[test]
+    fn connect_error() {
+        block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap().to_string();
+            drop(listener);
+            let mut tcp = TcpTransporter::new(&addr);
+            let e = tcp.open().await.unwrap_err();
+            let e = e.downcast_ref::<std::io::Error>().unwrap();
+            assert_eq!(e.kind(), std::io::ErrorKind::ConnectionRefused);
+        })
+    }
+}
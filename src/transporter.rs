@@ -1,10 +1,394 @@
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
 use async_trait::async_trait;
+use bytes::{Buf, BytesMut};
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{timeout, timeout_at, Instant};
+use tokio_util::codec::Decoder;
 
-use crate::{error::Error, frame::{Frame, ProtocolDataUnit}};
+use crate::{
+    error::Error,
+    frame::{Frame, ProtocolDataUnit},
+};
 
 #[async_trait]
 pub trait Transporter {
     async fn send(&mut self, adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error>;
+    /// 只发送不等待应答，用于广播等从站不应答的命令，返回写入的字节数
+    async fn write(&mut self, adu: &[u8]) -> Result<usize, Error> {
+        let _ = adu;
+        Err("sending without waiting for reply is not supported".into())
+    }
     async fn open(&mut self) -> Result<(), Error>;
     async fn close(&mut self) -> Result<(), Error>;
-}
\ No newline at end of file
+    /// 丢弃已收到但尚未读取的数据，返回丢弃的字节数
+    async fn flush_input(&mut self) -> Result<usize, Error> {
+        Ok(0)
+    }
+    /// 开始多帧交互（后续帧、写后读回等），共享总线的 Transporter 在 end_transaction 之前独占总线。
+    /// 可嵌套，每次 begin_transaction 成功后都要调用一次 end_transaction
+    fn begin_transaction(&mut self) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+    /// 结束多帧交互
+    fn end_transaction(&mut self) {}
+}
+
+/// 一帧最长字节数：4 字节前导 + 帧头 12 字节 + 数据域 200 字节 + 校验码及结束符
+pub const MAX_FRAME_LEN: usize = 4 + 12 + 200 + 2;
+/// 标准规定的电表最大应答延时
+const MAX_RESPONSE_DELAY: Duration = Duration::from_millis(500);
+/// 标准规定的字节之间最大停顿时间
+const MAX_BYTE_GAP: Duration = Duration::from_millis(500);
+/// 按波特率计算超时时的连接超时，只用于网络连接
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 通信超时设置
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeouts {
+    /// 建立网络连接
+    pub connect: Duration,
+    /// 写入请求帧
+    pub write: Duration,
+    /// 发送完成后等待应答的第一个字节
+    pub first_byte: Duration,
+    /// 应答帧相邻字节之间
+    pub inter_byte: Duration,
+    /// 发送完成后接收完整应答帧
+    pub frame: Duration,
+}
+
+impl Timeouts {
+    /// 所有超时均为 d
+    pub fn fixed(d: Duration) -> Self {
+        Self {
+            connect: d,
+            write: d,
+            first_byte: d,
+            inter_byte: d,
+            frame: d,
+        }
+    }
+    /// 按波特率计算：每字节 11 位（8 数据位、偶校验、1 停止位），按最长帧计算传输时间
+    pub fn for_baud_rate(baud_rate: u32) -> Self {
+        let byte = Duration::from_secs_f64(11.0 / baud_rate.max(1) as f64);
+        let frame = byte * MAX_FRAME_LEN as u32;
+        Self {
+            connect: CONNECT_TIMEOUT,
+            write: frame + Duration::from_millis(100),
+            first_byte: MAX_RESPONSE_DELAY + byte * 4,
+            inter_byte: MAX_BYTE_GAP,
+            frame: MAX_RESPONSE_DELAY + frame + Duration::from_millis(100),
+        }
+    }
+}
+
+/// 发送或接收超时，括号内为超时时间
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutError {
+    Connect(Duration),
+    Send(Duration),
+    /// 等待应答的第一个字节
+    FirstByte(Duration),
+    /// 等待应答帧的下一个字节
+    NextByte(Duration),
+    /// 等待完整应答帧
+    Frame(Duration),
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(d) => write!(f, "connection timed out after {:?}", d),
+            Self::Send(d) => write!(f, "send timeout after {:?}", d),
+            Self::FirstByte(d) => write!(f, "read timeout after {:?} waiting for first byte", d),
+            Self::NextByte(d) => write!(f, "read timeout after {:?} waiting for next byte", d),
+            Self::Frame(d) => write!(f, "read timeout after {:?} waiting for frame", d),
+        }
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/// 串口或连接未打开
+pub(crate) fn not_open(what: &str) -> Error {
+    Box::new(io::Error::new(
+        io::ErrorKind::NotConnected,
+        format!("{} is not opend", what),
+    ))
+}
+
+/// 在 timeouts 内写入请求帧
+pub(crate) async fn write_frame<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    adu: &[u8],
+    timeouts: &Timeouts,
+) -> Result<usize, Error> {
+    let write = async {
+        stream.write_all(adu).await?;
+        stream.flush().await
+    };
+    match timeout(timeouts.write, write).await {
+        Ok(r) => r.map(|_| adu.len()).map_err(Into::into),
+        Err(_) => Err(Box::new(TimeoutError::Send(timeouts.write))),
+    }
+}
+
+/// 从 stream 读取一帧，buf 中为已收到但未解码的字节。
+/// 无法解码时跳到下一个 68H 重新同步，直到 deadline；超时前收到过无法解码的字节时返回解码错误
+async fn read_frame<S, D>(
+    stream: &mut S,
+    codec: &mut D,
+    buf: &mut BytesMut,
+    timeouts: &Timeouts,
+    deadline: Instant,
+) -> Result<ProtocolDataUnit, Error>
+where
+    S: AsyncRead + Unpin,
+    D: Decoder<Item = ProtocolDataUnit, Error = Error>,
+{
+    let mut discarded: Option<Error> = None;
+    loop {
+        match codec.decode(buf) {
+            Ok(Some(pdu)) => return Ok(pdu),
+            Ok(None) => {}
+            // 丢弃到下一个帧起始符之前的字节（干扰、线路噪声等），继续解码之后的数据
+            Err(e) => {
+                let skip = buf[1..]
+                    .iter()
+                    .position(|b| *b == 0x68)
+                    .map_or(buf.len(), |i| i + 1);
+                buf.advance(skip);
+                discarded = Some(e);
+                continue;
+            }
+        }
+        let (wait, error): (_, fn(Duration) -> TimeoutError) = if buf.is_empty() {
+            (timeouts.first_byte, TimeoutError::FirstByte)
+        } else {
+            (timeouts.inter_byte, TimeoutError::NextByte)
+        };
+        let until = deadline.min(Instant::now() + wait);
+        match timeout_at(until, stream.read_buf(buf)).await {
+            Ok(Ok(0)) => {
+                return Err(Box::new(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed while reading",
+                )))
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return Err(e.into()),
+            // 超时同样丢弃未收完的帧，避免与下一次请求的应答拼接
+            Err(_) if until == deadline => {
+                buf.clear();
+                return Err(discarded.unwrap_or(Box::new(TimeoutError::Frame(timeouts.frame))));
+            }
+            Err(_) => {
+                buf.clear();
+                return Err(discarded.unwrap_or(Box::new(error(wait))));
+            }
+        }
+    }
+}
+
+/// 丢弃 buf 及 stream 中当前可读的数据，返回丢弃的字节数
+pub(crate) async fn drain<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    buf: &mut BytesMut,
+) -> Result<usize, Error> {
+    let mut n = buf.len();
+    buf.clear();
+    // 超时为零时只读取已到达的数据
+    while let Ok(r) = timeout(Duration::ZERO, stream.read_buf(buf)).await {
+        match r? {
+            0 => break,
+            len => n += len,
+        }
+        buf.clear();
+    }
+    Ok(n)
+}
+
+/// 接收 request 的应答，丢弃不相关的帧（上次超时请求的迟到应答、其他电表的应答、
+/// 半双工适配器回显的请求帧等）并计入 stale
+pub(crate) async fn receive_reply<S, D>(
+    stream: &mut S,
+    codec: &mut D,
+    buf: &mut BytesMut,
+    request: &ProtocolDataUnit,
+    timeouts: &Timeouts,
+    stale: &mut u64,
+) -> Result<Option<ProtocolDataUnit>, Error>
+where
+    S: AsyncRead + Unpin,
+    D: Decoder<Item = ProtocolDataUnit, Error = Error>,
+{
+    let deadline = Instant::now() + timeouts.frame;
+    loop {
+        let pdu = read_frame(stream, codec, buf, timeouts, deadline).await?;
+        if pdu.is_reply_to(request) {
+            return Ok(Some(pdu));
+        }
+        *stale += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::duplex;
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::frame::DecodeError;
+    use crate::rs485::RS485Codec;
+
+    /// 只实现必需方法的 Transporter
+    struct Minimal;
+
+    #[async_trait]
+    impl Transporter for Minimal {
+        async fn send(&mut self, _adu: &[u8]) -> Result<Option<ProtocolDataUnit>, Error> {
+            Ok(None)
+        }
+        async fn open(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+        async fn close(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn default_methods() {
+        block_on(async {
+            let mut t = Minimal;
+            assert!(t.write(&[0x68]).await.is_err());
+            assert_eq!(t.flush_input().await.unwrap(), 0);
+        })
+    }
+    #[test]
+    fn timeouts() {
+        let t = Timeouts::for_baud_rate(2400);
+        // 2400bps 时最长帧约 1s
+        assert!(t.frame > Duration::from_millis(1500) && t.frame < Duration::from_millis(1700));
+        assert!(Timeouts::for_baud_rate(9600).write < t.write);
+    }
+    #[test]
+    fn discard_stale_frames() {
+        block_on(async {
+            let addr = hex::decode("202208310002").unwrap();
+            let di = vec![vec![0x00, 0x01, 0x00, 0x00]];
+            let request = ProtocolDataUnit::from_cmd_2(addr.clone(), 0x11, &di).unwrap();
+            let other = vec![vec![0x00, 0x02, 0x00, 0x00]];
+            let late = ProtocolDataUnit::from_cmd_2(addr.clone(), 0x91, &other).unwrap();
+            let reply = ProtocolDataUnit::from_cmd_2(addr, 0x91, &di).unwrap();
+            let (mut meter, mut stream) = duplex(1024);
+            for frame in [request.clone(), late, reply.clone()] {
+                let adu: Vec<u8> = frame.into();
+                meter.write_all(&adu).await.unwrap();
+            }
+            let timeouts = Timeouts {
+                frame: Duration::from_millis(500),
+                ..Timeouts::fixed(Duration::from_millis(50))
+            };
+            let mut buf = BytesMut::new();
+            let mut stale = 0;
+            let r = receive_reply(
+                &mut stream,
+                &mut RS485Codec,
+                &mut buf,
+                &request,
+                &timeouts,
+                &mut stale,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(r.data(), reply.data());
+            assert_eq!(stale, 2);
+            // 只收到半帧时按字节间超时报错
+            let adu: Vec<u8> = request.clone().into();
+            meter.write_all(&adu[..10]).await.unwrap();
+            let e = receive_reply(
+                &mut stream,
+                &mut RS485Codec,
+                &mut buf,
+                &request,
+                &timeouts,
+                &mut stale,
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(
+                *e.downcast_ref::<TimeoutError>().unwrap(),
+                TimeoutError::NextByte(Duration::from_millis(50))
+            );
+            assert!(buf.is_empty());
+            // 半帧超时后的完整应答正常接收
+            let adu: Vec<u8> = reply.clone().into();
+            meter.write_all(&adu).await.unwrap();
+            let r = receive_reply(
+                &mut stream,
+                &mut RS485Codec,
+                &mut buf,
+                &request,
+                &timeouts,
+                &mut stale,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(r.data(), reply.data());
+        })
+    }
+    #[test]
+    fn resync() {
+        block_on(async {
+            let addr = hex::decode("202208310002").unwrap();
+            let di = vec![vec![0x00, 0x01, 0x00, 0x00]];
+            let request = ProtocolDataUnit::from_cmd_2(addr.clone(), 0x11, &di).unwrap();
+            let reply = ProtocolDataUnit::from_cmd_2(addr, 0x91, &di).unwrap();
+            let (mut meter, mut stream) = duplex(1024);
+            let timeouts = Timeouts {
+                frame: Duration::from_millis(500),
+                ..Timeouts::fixed(Duration::from_millis(50))
+            };
+            let mut buf = BytesMut::new();
+            let mut stale = 0;
+            // 干扰字节之后的应答正常接收
+            let adu: Vec<u8> = reply.clone().into();
+            meter.write_all(&[0x00]).await.unwrap();
+            meter.write_all(&adu).await.unwrap();
+            let r = receive_reply(
+                &mut stream,
+                &mut RS485Codec,
+                &mut buf,
+                &request,
+                &timeouts,
+                &mut stale,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(r.data(), reply.data());
+            assert_eq!(stale, 0);
+            // 只收到干扰字节时返回解码错误
+            meter.write_all(&[0x00, 0x01]).await.unwrap();
+            let e = receive_reply(
+                &mut stream,
+                &mut RS485Codec,
+                &mut buf,
+                &request,
+                &timeouts,
+                &mut stale,
+            )
+            .await
+            .unwrap_err();
+            assert!(e.downcast_ref::<DecodeError>().is_some());
+            assert!(buf.is_empty());
+        })
+    }
+}
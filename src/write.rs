@@ -0,0 +1,99 @@
+use crate::address::MeterAddress;
+use crate::catalog::DataId;
+use crate::error::Error;
+use crate::frame::ProtocolDataUnit;
+use crate::password::{OperatorCode, Password};
+use crate::transporter::Transporter;
+
+/// 写数据命令（控制码 14H）
+#[derive(Clone, Debug)]
+pub struct Write {
+    pub di: DataId,
+    pub password: Password,
+    pub operator: OperatorCode,
+    /// 数据，每段高字节在前，各段按传输顺序排列
+    pub data: Vec<Vec<u8>>,
+}
+
+impl Write {
+    pub fn new(di: DataId, password: Password, operator: OperatorCode, data: Vec<Vec<u8>>) -> Self {
+        Self {
+            di,
+            password,
+            operator,
+            data,
+        }
+    }
+    pub fn to_pdu(&self, addr: &MeterAddress) -> Result<ProtocolDataUnit, Error> {
+        let mut segments = vec![
+            self.di.bytes().to_vec(),
+            self.password.segment(),
+            self.operator.segment(),
+        ];
+        segments.extend(self.data.iter().cloned());
+        let len = segments.iter().map(|s| s.len()).sum::<usize>();
+        if len > 200 {
+            return Err(
+                format!("writing `{}`: data field too long ({} bytes)", self.di, len).into(),
+            );
+        }
+        ProtocolDataUnit::from_cmd_2(addr.to_vec(), 0x14, &segments)
+    }
+    /// 正常应答 94H；异常应答 D4H 时返回 Exception，包含电表拒绝的原因
+    pub fn check_reply(reply: &ProtocolDataUnit) -> Result<(), Error> {
+        reply.check_reply(0x14)
+    }
+}
+
+/// 写数据
+pub async fn write<T: Transporter + ?Sized>(
+    transporter: &mut T,
+    addr: &MeterAddress,
+    request: &Write,
+) -> Result<(), Error> {
+    let adu: Vec<u8> = request.to_pdu(addr)?.into();
+    match transporter.send(&adu).await? {
+        Some(reply) => Write::check_reply(&reply),
+        None => Err(format!("no response writing `{}`", request.di).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_test::block_on;
+
+    use super::*;
+    use crate::error::Exception;
+    use crate::mock::MockTransporter;
+
+    const ADDR: &str = "202208310002";
+
+    fn request() -> Write {
+        Write::new(
+            DataId(0x04000103),
+            "02123456".parse().unwrap(),
+            "01020304".parse().unwrap(),
+            vec![vec![0x15]],
+        )
+    }
+
+    #[test]
+    fn to_pdu() {
+        let addr = ADDR.parse().unwrap();
+        let pdu = request().to_pdu(&addr).unwrap();
+        assert_eq!(pdu.c(), 0x14);
+        // DI0~DI3 PA P0 P1 P2 C0~C3 数据
+        assert_eq!(hex::encode(pdu.payload()), "03010004025634120403020115");
+    }
+    #[test]
+    fn write_refused() {
+        block_on(async {
+            let mut t = MockTransporter::new();
+            t.reply(ADDR, 0x94, &[]).reply(ADDR, 0xD4, &[vec![0x04]]);
+            let addr = ADDR.parse().unwrap();
+            write(&mut t, &addr, &request()).await.unwrap();
+            let e = write(&mut t, &addr, &request()).await.unwrap_err();
+            assert!(e.downcast_ref::<Exception>().unwrap().unauthorized());
+        })
+    }
+}